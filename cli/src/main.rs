@@ -1,4 +1,4 @@
-use mlang::{Reader as _, BaseReader, ParseError};
+use mlang::{Reader as _, BaseReader, Interpreter, ParseError};
 use std::io;
 
 fn rep(s: &str) {
@@ -24,14 +24,14 @@ fn rep(s: &str) {
 }
 
 fn main() -> io::Result<()> {
-	mlang::init();
+	let interp = Interpreter::new();
 
 	let stdin = io::stdin();
 	let mut input = String::new();
 
 	loop {
 		stdin.read_line(&mut input)?;
-		rep(&input[..]);
+		interp.enter(|| rep(&input[..]));
 		input.clear();
 	}
 }
@@ -0,0 +1,596 @@
+use core::cmp::Ordering;
+use core::fmt;
+
+/// An integer of any size, used for the values that do not fit a long.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BigInt {
+	negative: bool,
+	// little endian limbs without leading zeros, empty for zero
+	mag: Vec<u32>,
+}
+
+impl BigInt {
+	fn new(negative: bool, mut mag: Vec<u32>) -> Self {
+		trim(&mut mag);
+		Self { negative: negative && !mag.is_empty(), mag }
+	}
+
+	/// Parses unsigned digits in `radix`, `None` if there are no digits or
+	/// one of them is invalid.
+	pub fn from_digits(digits: &str, radix: u32) -> Option<Self> {
+		if digits.is_empty() {
+			return None
+		}
+		let mut mag = Vec::new();
+		for c in digits.chars() {
+			mul_add_small(&mut mag, radix, c.to_digit(radix)?);
+		}
+		Some(Self::new(false, mag))
+	}
+
+	/// The integer part of `n`, `None` if it is not finite.
+	pub fn from_f64(n: f64) -> Option<Self> {
+		if !n.is_finite() {
+			return None
+		}
+		let bits = n.abs().to_bits();
+		let exp = (bits >> 52) as usize;
+		if exp < 1023 {
+			return Some(Self::default())
+		}
+		// the mantissa with its implicit bit, scaled by the exponent
+		let mantissa = bits & ((1 << 52) - 1) | 1 << 52;
+		let mag = [mantissa as u32, (mantissa >> 32) as u32];
+		let mag = match exp.checked_sub(1075) {
+			Some(by) => shl_mag(&mag, by),
+			None => shr_mag(&mag, 1075 - exp),
+		};
+		Some(Self::new(n < 0.0, mag))
+	}
+
+	/// The nearest float, infinite if the integer is too large.
+	pub fn to_f64(&self) -> f64 {
+		let bits = self.bits();
+		let by = bits.saturating_sub(64);
+		let top = shr_mag(&self.mag, by);
+		let mut top = top.iter().rev().fold(0, |acc, &limb| acc << 32 | limb as u64);
+		// the bits shifted out only matter for rounding
+		if self.mag[..by / 32].iter().any(|&limb| limb != 0) || self.mag.get(by / 32).is_some_and(|limb| limb & ((1 << (by % 32)) - 1) != 0) {
+			top |= 1;
+		}
+		let mag = top as f64 * 2f64.powi(by.min(i32::MAX as usize) as i32);
+		if self.negative { -mag } else { mag }
+	}
+
+	pub fn to_isize(&self) -> Option<isize> {
+		if self.mag.len() > 2 {
+			return None
+		}
+		let mag = self.mag.iter().rev().fold(0, |acc, &limb| acc << 32 | limb as u64) as i128;
+		isize::try_from(if self.negative { -mag } else { mag }).ok()
+	}
+
+	pub fn is_zero(&self) -> bool {
+		self.mag.is_empty()
+	}
+
+	pub fn is_negative(&self) -> bool {
+		self.negative
+	}
+
+	pub fn is_odd(&self) -> bool {
+		self.mag.first().is_some_and(|limb| limb & 1 == 1)
+	}
+
+	/// Number of bits of the magnitude.
+	pub fn bits(&self) -> usize {
+		bits_mag(&self.mag)
+	}
+
+	pub fn neg(&self) -> Self {
+		Self::new(!self.negative, self.mag.clone())
+	}
+
+	pub fn abs(&self) -> Self {
+		Self::new(false, self.mag.clone())
+	}
+
+	pub fn add(&self, other: &Self) -> Self {
+		if self.negative == other.negative {
+			return Self::new(self.negative, add_mag(&self.mag, &other.mag))
+		}
+		match cmp_mag(&self.mag, &other.mag) {
+			Ordering::Less => Self::new(other.negative, sub_mag(&other.mag, &self.mag)),
+			_ => Self::new(self.negative, sub_mag(&self.mag, &other.mag)),
+		}
+	}
+
+	pub fn sub(&self, other: &Self) -> Self {
+		self.add(&other.neg())
+	}
+
+	pub fn mul(&self, other: &Self) -> Self {
+		Self::new(self.negative != other.negative, mul_mag(&self.mag, &other.mag))
+	}
+
+	/// Truncated quotient and remainder, `None` if `other` is zero.
+	pub fn div_rem(&self, other: &Self) -> Option<(Self, Self)> {
+		if other.is_zero() {
+			return None
+		}
+		let (quotient, rem) = div_rem_mag(&self.mag, &other.mag);
+		Some((Self::new(self.negative != other.negative, quotient), Self::new(self.negative, rem)))
+	}
+
+	/// Greatest common divisor, never negative.
+	pub fn gcd(&self, other: &Self) -> Self {
+		let (mut lhs, mut rhs) = (self.mag.clone(), other.mag.clone());
+		if cmp_mag(&lhs, &rhs) == Ordering::Less {
+			(lhs, rhs) = (rhs, lhs);
+		}
+		// Lehmer's algorithm: the steps of Euclid's algorithm are run on the
+		// leading bits while they give the same quotients as on the whole
+		// numbers, then applied to the whole numbers at once
+		while rhs.len() > 2 {
+			let by = bits_mag(&lhs) - 62;
+			let (mut u, mut v) = (top(&lhs, by), top(&rhs, by));
+			let (mut a, mut b, mut c, mut d) = (1, 0, 0, 1);
+			while v + c != 0 && v + d != 0 {
+				let q = (u + a) / (v + c);
+				if q != (u + b) / (v + d) {
+					break
+				}
+				(a, c) = (c, a - q * c);
+				(b, d) = (d, b - q * d);
+				(u, v) = (v, u - q * v);
+			}
+			(lhs, rhs) = match b {
+				0 => {
+					let (_, rem) = div_rem_mag(&lhs, &rhs);
+					(rhs, rem)
+				}
+				_ => (combine(&lhs, a, &rhs, b), combine(&lhs, c, &rhs, d)),
+			};
+		}
+		while !rhs.is_empty() {
+			let (_, rem) = div_rem_mag(&lhs, &rhs);
+			(lhs, rhs) = (rhs, rem);
+		}
+		Self::new(false, lhs)
+	}
+
+	pub fn pow(&self, mut exp: usize) -> Self {
+		let mut base = self.clone();
+		let mut acc = Self::from(1);
+		while exp > 0 {
+			if exp & 1 == 1 {
+				acc = acc.mul(&base);
+			}
+			exp >>= 1;
+			if exp > 0 {
+				base = base.mul(&base);
+			}
+		}
+		acc
+	}
+
+	pub fn shl(&self, by: usize) -> Self {
+		Self::new(self.negative, shl_mag(&self.mag, by))
+	}
+
+	/// Arithmetic shift, rounds towards negative infinity like on longs.
+	pub fn shr(&self, by: usize) -> Self {
+		if !self.negative {
+			return Self::new(false, shr_mag(&self.mag, by))
+		}
+		// -((|n| - 1 >> by) + 1)
+		let mag = shr_mag(&sub_mag(&self.mag, &[1]), by);
+		Self::new(true, add_mag(&mag, &[1]))
+	}
+
+	// bitwise operations work on the infinite two's complement
+	pub fn and(&self, other: &Self) -> Self {
+		self.bitwise(other, |lhs, rhs| lhs & rhs)
+	}
+
+	pub fn or(&self, other: &Self) -> Self {
+		self.bitwise(other, |lhs, rhs| lhs | rhs)
+	}
+
+	pub fn xor(&self, other: &Self) -> Self {
+		self.bitwise(other, |lhs, rhs| lhs ^ rhs)
+	}
+
+	pub fn not(&self) -> Self {
+		self.neg().sub(&Self::from(1))
+	}
+
+	fn bitwise(&self, other: &Self, f: impl Fn(u32, u32) -> u32) -> Self {
+		let len = self.mag.len().max(other.mag.len()) + 1;
+		let (lhs, rhs) = (self.twos(len), other.twos(len));
+		let mut limbs: Vec<u32> = lhs.iter().zip(&rhs).map(|(&lhs, &rhs)| f(lhs, rhs)).collect();
+		let negative = limbs.last().is_some_and(|last| last >> 31 == 1);
+		if negative {
+			negate(&mut limbs);
+		}
+		Self::new(negative, limbs)
+	}
+
+	fn twos(&self, len: usize) -> Vec<u32> {
+		let mut limbs = self.mag.clone();
+		limbs.resize(len, 0);
+		if self.negative {
+			negate(&mut limbs);
+		}
+		limbs
+	}
+}
+
+impl From<isize> for BigInt {
+	fn from(n: isize) -> Self {
+		let mag = n.unsigned_abs() as u64;
+		Self::new(n < 0, vec![mag as u32, (mag >> 32) as u32])
+	}
+}
+
+impl Ord for BigInt {
+	fn cmp(&self, other: &Self) -> Ordering {
+		match (self.negative, other.negative) {
+			(false, true) => Ordering::Greater,
+			(true, false) => Ordering::Less,
+			(false, false) => cmp_mag(&self.mag, &other.mag),
+			(true, true) => cmp_mag(&other.mag, &self.mag),
+		}
+	}
+}
+
+impl PartialOrd for BigInt {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl fmt::Display for BigInt {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		// chunks of 9 decimal digits, least significant first
+		let mut chunks = Vec::new();
+		let mut mag = self.mag.clone();
+		while !mag.is_empty() {
+			let (quotient, rem) = div_rem_small(&mag, 1_000_000_000);
+			chunks.push(rem);
+			mag = quotient;
+		}
+		let Some((first, rest)) = chunks.split_last() else {
+			return write!(f, "0")
+		};
+		if self.negative {
+			write!(f, "-")?;
+		}
+		write!(f, "{first}")?;
+		rest.iter().rev().try_for_each(|chunk| write!(f, "{chunk:09}"))
+	}
+}
+
+fn trim(mag: &mut Vec<u32>) {
+	while mag.last() == Some(&0) {
+		mag.pop();
+	}
+}
+
+// two's complement negation in place
+fn negate(limbs: &mut [u32]) {
+	let mut carry = 1;
+	for limb in limbs {
+		let sum = !*limb as u64 + carry;
+		*limb = sum as u32;
+		carry = sum >> 32;
+	}
+}
+
+fn cmp_mag(lhs: &[u32], rhs: &[u32]) -> Ordering {
+	lhs.len().cmp(&rhs.len()).then_with(|| lhs.iter().rev().cmp(rhs.iter().rev()))
+}
+
+fn add_mag(lhs: &[u32], rhs: &[u32]) -> Vec<u32> {
+	let (long, short) = if lhs.len() >= rhs.len() { (lhs, rhs) } else { (rhs, lhs) };
+	let mut sum = Vec::with_capacity(long.len() + 1);
+	let mut carry = 0;
+	for (i, &limb) in long.iter().enumerate() {
+		let limb_sum = limb as u64 + short.get(i).copied().unwrap_or(0) as u64 + carry;
+		sum.push(limb_sum as u32);
+		carry = limb_sum >> 32;
+	}
+	sum.push(carry as u32);
+	trim(&mut sum);
+	sum
+}
+
+// `lhs` must not be less than `rhs`
+fn sub_mag(lhs: &[u32], rhs: &[u32]) -> Vec<u32> {
+	let mut diff = Vec::with_capacity(lhs.len());
+	let mut borrow = 0;
+	for (i, &limb) in lhs.iter().enumerate() {
+		let (limb, over) = limb.overflowing_sub(rhs.get(i).copied().unwrap_or(0));
+		let (limb, under) = limb.overflowing_sub(borrow);
+		diff.push(limb);
+		borrow = (over || under) as u32;
+	}
+	trim(&mut diff);
+	diff
+}
+
+fn mul_mag(lhs: &[u32], rhs: &[u32]) -> Vec<u32> {
+	let mut product = vec![0u32; lhs.len() + rhs.len()];
+	for (i, &x) in lhs.iter().enumerate() {
+		let mut carry = 0;
+		for (j, &y) in rhs.iter().enumerate() {
+			let limb = x as u64 * y as u64 + product[i + j] as u64 + carry;
+			product[i + j] = limb as u32;
+			carry = limb >> 32;
+		}
+		product[i + rhs.len()] = carry as u32;
+	}
+	trim(&mut product);
+	product
+}
+
+fn mul_add_small(mag: &mut Vec<u32>, mul: u32, add: u32) {
+	let mut carry = add as u64;
+	for limb in mag.iter_mut() {
+		let product = *limb as u64 * mul as u64 + carry;
+		*limb = product as u32;
+		carry = product >> 32;
+	}
+	if carry > 0 {
+		mag.push(carry as u32);
+	}
+}
+
+fn div_rem_small(mag: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+	let mut quotient = vec![0; mag.len()];
+	let mut rem = 0;
+	for (i, &limb) in mag.iter().enumerate().rev() {
+		let dividend = rem << 32 | limb as u64;
+		quotient[i] = (dividend / divisor as u64) as u32;
+		rem = dividend % divisor as u64;
+	}
+	trim(&mut quotient);
+	(quotient, rem as u32)
+}
+
+// `divisor` must not be zero
+fn div_rem_mag(mag: &[u32], divisor: &[u32]) -> (Vec<u32>, Vec<u32>) {
+	if cmp_mag(mag, divisor) == Ordering::Less {
+		return (Vec::new(), mag.to_vec())
+	}
+	if let [divisor] = divisor {
+		let (quotient, rem) = div_rem_small(mag, *divisor);
+		let mut rem = vec![rem];
+		trim(&mut rem);
+		return (quotient, rem)
+	}
+	// Knuth's algorithm D, the operands are shifted so that the divisor has
+	// its top bit set, then each limb of the quotient is estimated from the
+	// leading limbs and corrected at most twice
+	let shift = divisor.last().unwrap().leading_zeros() as usize;
+	let divisor = shl_mag(divisor, shift);
+	let mut rem = shl_mag(mag, shift);
+	rem.resize(mag.len() + 1, 0);
+	let n = divisor.len();
+	let (high, next) = (divisor[n - 1] as u64, divisor[n - 2] as u64);
+	let mut quotient = vec![0u32; rem.len() - n];
+	for j in (0..quotient.len()).rev() {
+		let lead = (rem[j + n] as u64) << 32 | rem[j + n - 1] as u64;
+		let (mut q, mut r) = (lead / high, lead % high);
+		while q >> 32 != 0 || q * next > (r << 32 | rem[j + n - 2] as u64) {
+			q -= 1;
+			r += high;
+			if r >> 32 != 0 {
+				break
+			}
+		}
+		// subtracts `q` times the divisor
+		let mut borrow: i64 = 0;
+		for (i, &limb) in divisor.iter().enumerate() {
+			let product = q * limb as u64;
+			let diff = rem[i + j] as i64 - borrow - (product & 0xffff_ffff) as i64;
+			rem[i + j] = diff as u32;
+			borrow = (product >> 32) as i64 - (diff >> 32);
+		}
+		let diff = rem[j + n] as i64 - borrow;
+		rem[j + n] = diff as u32;
+		// the estimate was one too large, the divisor is added back
+		if diff < 0 {
+			q -= 1;
+			let mut carry = 0;
+			for (i, &limb) in divisor.iter().enumerate() {
+				let sum = rem[i + j] as u64 + limb as u64 + carry;
+				rem[i + j] = sum as u32;
+				carry = sum >> 32;
+			}
+			rem[j + n] = rem[j + n].wrapping_add(carry as u32);
+		}
+		quotient[j] = q as u32;
+	}
+	trim(&mut quotient);
+	rem.truncate(n);
+	(quotient, shr_mag(&rem, shift))
+}
+
+fn bits_mag(mag: &[u32]) -> usize {
+	mag.last().map_or(0, |last| mag.len() * 32 - last.leading_zeros() as usize)
+}
+
+// the bits of `mag` from `by` on, there must be at most 64 of them
+fn top(mag: &[u32], by: usize) -> i128 {
+	shr_mag(mag, by).iter().rev().fold(0, |acc, &limb| acc << 32 | limb as i128)
+}
+
+// `a * lhs + b * rhs`, which must not be negative, in one pass. The
+// cofactors are below 2^63.
+fn combine(lhs: &[u32], a: i128, rhs: &[u32], b: i128) -> Vec<u32> {
+	let mut sum = Vec::with_capacity(lhs.len() + 2);
+	let mut carry: i128 = 0;
+	for i in 0..lhs.len().max(rhs.len()) {
+		let limb = |mag: &[u32]| mag.get(i).copied().unwrap_or(0) as i128;
+		carry += a * limb(lhs) + b * limb(rhs);
+		sum.push(carry as u32);
+		carry >>= 32;
+	}
+	sum.push(carry as u32);
+	sum.push((carry >> 32) as u32);
+	trim(&mut sum);
+	sum
+}
+
+fn shl_mag(mag: &[u32], by: usize) -> Vec<u32> {
+	if mag.is_empty() {
+		return Vec::new()
+	}
+	let (limbs, bits) = (by / 32, (by % 32) as u32);
+	let mut shifted = vec![0; limbs];
+	if bits == 0 {
+		shifted.extend_from_slice(mag);
+		return shifted
+	}
+	let mut carry = 0;
+	for &limb in mag {
+		shifted.push(limb << bits | carry);
+		carry = limb >> (32 - bits);
+	}
+	shifted.push(carry);
+	trim(&mut shifted);
+	shifted
+}
+
+fn shr_mag(mag: &[u32], by: usize) -> Vec<u32> {
+	let (limbs, bits) = (by / 32, (by % 32) as u32);
+	let Some(mag) = mag.get(limbs..) else {
+		return Vec::new()
+	};
+	let mut shifted: Vec<u32> = mag.iter().enumerate().map(|(i, &limb)| {
+		let high = match mag.get(i + 1) {
+			Some(&next) if bits > 0 => next << (32 - bits),
+			_ => 0,
+		};
+		limb >> bits | high
+	}).collect();
+	trim(&mut shifted);
+	shifted
+}
+
+#[cfg(test)]
+mod tests {
+	use super::BigInt;
+
+	fn big(n: i128) -> BigInt {
+		let digits = BigInt::from_digits(&n.unsigned_abs().to_string(), 10).unwrap();
+		if n < 0 { digits.neg() } else { digits }
+	}
+
+	// xorshift, so that failures can be reproduced
+	struct Rng(u64);
+
+	impl Rng {
+		fn next(&mut self) -> u64 {
+			self.0 ^= self.0 << 13;
+			self.0 ^= self.0 >> 7;
+			self.0 ^= self.0 << 17;
+			self.0
+		}
+
+		// a signed number of at most `bits` bits
+		fn int(&mut self, bits: u32) -> i128 {
+			let bits = self.next() as u32 % bits + 1;
+			let n = ((self.next() as u128) << 64 | self.next() as u128) >> (128 - bits);
+			if self.next() & 1 == 1 { -(n as i128) } else { n as i128 }
+		}
+
+		// a number of at most `limbs` random limbs
+		fn big(&mut self, limbs: usize) -> BigInt {
+			let limbs = self.next() as usize % limbs + 1;
+			let mag = (0..limbs).map(|_| self.next() as u32).collect();
+			BigInt::new(self.next() & 1 == 1, mag)
+		}
+	}
+
+	#[test]
+	fn arithmetic_matches_i128() {
+		let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+		for _ in 0..10_000 {
+			let (x, y) = (rng.int(126), rng.int(126));
+			let (a, b) = (big(x), big(y));
+			assert_eq!(a.add(&b), big(x + y), "{x} + {y}");
+			assert_eq!(a.sub(&b), big(x - y), "{x} - {y}");
+			if y != 0 {
+				assert_eq!(a.div_rem(&b), Some((big(x / y), big(x % y))), "{x} / {y}");
+			}
+			assert_eq!(a.cmp(&b), x.cmp(&y), "{x} <=> {y}");
+			let (x, y) = (x >> 63, y >> 63);
+			assert_eq!(big(x).mul(&big(y)), big(x * y), "{x} * {y}");
+		}
+	}
+
+	#[test]
+	fn bitwise_matches_i128() {
+		let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+		for _ in 0..10_000 {
+			let (x, y) = (rng.int(120), rng.int(120));
+			let (a, b) = (big(x), big(y));
+			assert_eq!(a.and(&b), big(x & y), "{x} & {y}");
+			assert_eq!(a.or(&b), big(x | y), "{x} | {y}");
+			assert_eq!(a.xor(&b), big(x ^ y), "{x} ^ {y}");
+			assert_eq!(a.not(), big(!x), "!{x}");
+			let by = rng.next() as usize % 100;
+			assert_eq!(a.shr(by), big(x >> by), "{x} >> {by}");
+			assert_eq!(big(x >> 100).shl(by % 7), big(x >> 100 << (by % 7)), "{x} << {by}");
+		}
+	}
+
+	#[test]
+	fn conversions() {
+		assert_eq!(BigInt::from_digits("", 10), None);
+		assert_eq!(BigInt::from_digits("12a", 10), None);
+		assert_eq!(BigInt::from_digits("dead_beef", 16), None);
+		assert_eq!(BigInt::from_digits("deadbeef", 16), Some(big(0xdead_beef)));
+		assert_eq!(big(-(1 << 100)).to_string(), "-1267650600228229401496703205376");
+		assert_eq!(big(i64::MIN as i128).to_isize(), Some(isize::MIN));
+		assert_eq!(big(1 << 63).to_isize(), None);
+		assert_eq!(BigInt::from_f64(-2.5e20), Some(big(-250_000_000_000_000_000_000)));
+		assert_eq!(BigInt::from_f64(-0.75), Some(big(0)));
+		assert_eq!(BigInt::from_f64(f64::NAN), None);
+		// rounds to nearest, ties to even
+		assert_eq!(big((1 << 80) + (1 << 27)).to_f64(), 2f64.powi(80));
+		assert_eq!(big((1 << 80) + (1 << 27) + 1).to_f64(), 2f64.powi(80) + 2f64.powi(28));
+		assert_eq!(big(-(1 << 80) - (3 << 27)).to_f64(), -2f64.powi(80) - 2f64.powi(29));
+		assert_eq!(big(3).pow(1000).to_f64(), f64::INFINITY);
+	}
+
+	#[test]
+	fn large_division_and_gcd() {
+		let mut rng = Rng(0xd1b5_4a32_d192_ed03);
+		for _ in 0..2_000 {
+			let (n, d) = (rng.big(12), rng.big(8));
+			if let Some((q, r)) = n.div_rem(&d) {
+				assert_eq!(q.mul(&d).add(&r), n);
+				assert!(r.abs() < d.abs() && (r.is_zero() || r.is_negative() == n.is_negative()));
+			}
+			// with a known common factor
+			let g = rng.big(6).abs();
+			let (x, y) = (n.mul(&g), d.mul(&g));
+			let gcd = x.gcd(&y);
+			assert_eq!(gcd, y.gcd(&x));
+			assert!(!gcd.is_negative());
+			if !gcd.is_zero() {
+				let (x, rem) = x.div_rem(&gcd).unwrap();
+				assert!(rem.is_zero());
+				let (y, rem) = y.div_rem(&gcd).unwrap();
+				assert!(rem.is_zero());
+				assert_eq!(x.gcd(&y), big(1));
+				assert!(gcd.div_rem(&g).unwrap().1.is_zero());
+			}
+		}
+		assert_eq!(big(0).gcd(&big(-12)), big(12));
+		assert_eq!(big(0).gcd(&big(0)), big(0));
+	}
+}
@@ -0,0 +1,230 @@
+use crate::{Op, Value, BigInt, Ratio, EvalError, Interpreter, nil};
+use crate::eval::truth;
+
+/// Conversion of a Rust value into an mlang value.
+pub trait IntoValue {
+	fn into_value(self) -> Op;
+}
+
+/// Conversion of an mlang value into a Rust value.
+pub trait FromValue: Sized {
+	fn from_value(op: Op) -> Result<Self, EvalError>;
+}
+
+impl IntoValue for Op {
+	fn into_value(self) -> Op {
+		self
+	}
+}
+
+impl FromValue for Op {
+	fn from_value(op: Op) -> Result<Self, EvalError> {
+		Ok(op)
+	}
+}
+
+impl IntoValue for isize {
+	fn into_value(self) -> Op {
+		Op::long(self)
+	}
+}
+
+impl FromValue for isize {
+	fn from_value(op: Op) -> Result<Self, EvalError> {
+		match op.value() {
+			Value::Long(n) => Ok(n),
+			Value::Big(_) => Err(EvalError::Overflow),
+			_ => Err(EvalError::RequireLong(op)),
+		}
+	}
+}
+
+impl IntoValue for BigInt {
+	fn into_value(self) -> Op {
+		Op::big(self)
+	}
+}
+
+impl FromValue for BigInt {
+	fn from_value(op: Op) -> Result<Self, EvalError> {
+		match op.value() {
+			Value::Long(n) => Ok(BigInt::from(n)),
+			Value::Big(n) => Ok(n),
+			_ => Err(EvalError::RequireLong(op)),
+		}
+	}
+}
+
+impl IntoValue for Ratio {
+	fn into_value(self) -> Op {
+		Op::ratio(self)
+	}
+}
+
+impl FromValue for Ratio {
+	fn from_value(op: Op) -> Result<Self, EvalError> {
+		match op.value() {
+			Value::Long(n) => Ok(Ratio::from(BigInt::from(n))),
+			Value::Big(n) => Ok(Ratio::from(n)),
+			Value::Ratio(n) => Ok(n),
+			_ => Err(EvalError::RequireExact(op)),
+		}
+	}
+}
+
+impl IntoValue for f64 {
+	fn into_value(self) -> Op {
+		Op::float(self)
+	}
+}
+
+// exact numbers are converted to the nearest float
+impl FromValue for f64 {
+	fn from_value(op: Op) -> Result<Self, EvalError> {
+		match op.value() {
+			Value::Float(n) => Ok(n),
+			Value::Long(n) => Ok(n as f64),
+			Value::Big(n) => Ok(n.to_f64()),
+			Value::Ratio(n) => Ok(n.to_f64()),
+			_ => Err(EvalError::RequireNumber(op)),
+		}
+	}
+}
+
+impl IntoValue for bool {
+	fn into_value(self) -> Op {
+		truth(self)
+	}
+}
+
+impl FromValue for bool {
+	fn from_value(op: Op) -> Result<Self, EvalError> {
+		Ok(op.is_true())
+	}
+}
+
+// there is no string type, text is represented by symbols
+impl IntoValue for &str {
+	fn into_value(self) -> Op {
+		Op::symbol(self)
+	}
+}
+
+impl IntoValue for String {
+	fn into_value(self) -> Op {
+		Op::symbol(&self)
+	}
+}
+
+impl FromValue for String {
+	fn from_value(op: Op) -> Result<Self, EvalError> {
+		op.as_symbol().map(|s| s.to_string()).ok_or(EvalError::RequireSymbol(op))
+	}
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+	fn into_value(self) -> Op {
+		self.map_or(nil(), T::into_value)
+	}
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+	fn from_value(op: Op) -> Result<Self, EvalError> {
+		if op.is_null() {
+			return Ok(None)
+		}
+		T::from_value(op).map(Some)
+	}
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+	fn into_value(self) -> Op {
+		Op::list(self.into_iter().map(T::into_value))
+	}
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+	fn from_value(op: Op) -> Result<Self, EvalError> {
+		let mut list = op.iter();
+		let vec = list.by_ref().map(T::from_value).collect::<Result<_, _>>()?;
+		let rest = list.rest();
+		if !rest.is_null() {
+			return Err(EvalError::RequirePair(rest))
+		}
+		Ok(vec)
+	}
+}
+
+// tuples map to lists of fixed length, extra elements are ignored like extra
+// arguments of a primitive.
+macro_rules! tuple_value {
+	($($name:ident)*) => {
+		impl<$($name: IntoValue),*> IntoValue for ($($name,)*) {
+			#[allow(non_snake_case)]
+			fn into_value(self) -> Op {
+				let ($($name,)*) = self;
+				Op::list([$($name.into_value()),*])
+			}
+		}
+
+		impl<$($name: FromValue),*> FromValue for ($($name,)*) {
+			#[allow(non_snake_case, unused)]
+			fn from_value(op: Op) -> Result<Self, EvalError> {
+				let mut list = op.iter();
+				$( let $name = $name::from_value(list.next().ok_or(EvalError::TooFewArgs)?)?; )*
+				Ok(($($name,)*))
+			}
+		}
+	};
+}
+
+tuple_value!();
+tuple_value!(A);
+tuple_value!(A B);
+tuple_value!(A B C);
+tuple_value!(A B C D);
+tuple_value!(A B C D E);
+tuple_value!(A B C D E F);
+
+/// A Rust function taking and returning convertible values, see
+/// [`Interpreter::register_fn`].
+pub trait HostFn<Args> {
+	fn call(&self, args: Op) -> Result<Op, EvalError>;
+}
+
+macro_rules! host_fn {
+	($($name:ident)*) => {
+		impl<Fun, Ret, $($name),*> HostFn<($($name,)*)> for Fun
+		where
+			Fun: Fn($($name),*) -> Ret,
+			Ret: IntoValue,
+			$( $name: FromValue, )*
+		{
+			#[allow(non_snake_case)]
+			fn call(&self, args: Op) -> Result<Op, EvalError> {
+				let ($($name,)*) = <($($name,)*)>::from_value(args)?;
+				Ok(self($($name),*).into_value())
+			}
+		}
+	};
+}
+
+host_fn!();
+host_fn!(A);
+host_fn!(A B);
+host_fn!(A B C);
+host_fn!(A B C D);
+host_fn!(A B C D E);
+host_fn!(A B C D E F);
+
+impl Interpreter {
+	/// Defines a global primitive out of a typed Rust function such as
+	/// `|a: isize, b: isize| a.max(b)`, arguments and result are converted
+	/// with [`FromValue`] and [`IntoValue`].
+	pub fn register_fn<Args, F>(&self, name: &str, fun: F)
+	where
+		F: HostFn<Args> + 'static
+	{
+		self.register(name, false, move |args, _env| fun.call(args))
+	}
+}
@@ -1,76 +1,390 @@
-use crate::{Object, Op, nil, cons};
-use crate::GLOBALS;
+use crate::{Object, Op, Imp, nil, cons, globals, intern, context};
+use crate::gc::{self, Roots};
+
+mod syntax;
+pub mod num;
+
+// frames on the control stack, deeper evaluations fail
+const MAX_DEPTH: usize = 100_000;
+// evaluations nested through primitives, each one takes native stack
+const MAX_NESTING: usize = 128;
 
 #[derive(Debug)]
 pub enum Error {
 	Undefined(Op),
 	CanNotApply(Op),
 	RequireLong(Op),
+	RequireNumber(Op),
+	// an integer or a fraction
+	RequireExact(Op),
 	RequireSymbol(Op),
 	RequirePair(Op),
 	RequireExpr(Op),
 	TooFewArgs,
+	BadSyntax(Op),
+	StackOverflow,
+	DivisionByZero,
+	Overflow,
+	// a value given to `raise`
+	Raise(Op),
+	/// A continuation applied to a value, unwinds to the run it belongs to.
+	/// Not an error of the script: a host primitive calling back into the
+	/// interpreter must return it unchanged for the continuation to work.
+	Resume(Op, Op),
 }
 
-pub fn eval(op: Op, env: Op) -> Result<Op, Error> {
-	println!("EVAL {:?} IN {:?}", op, env);
-	match op.as_ref() {
-		None => {
-			Ok(nil())
-		}
-		Some(object) => match object {
-			Object::Symbol { .. } => {
-				let assoc_pair = assoc(op, env);
-				if assoc_pair.is_null() {
-					return Err(Error::Undefined(op))
-				}
-				Ok(cdr(assoc_pair))
+impl Error {
+	/// The error as seen by `catch`, a raised value as is and other errors
+	/// as a list of their kind and the offending value, e.g. `(undefined x)`.
+	/// An escaping continuation is `(resume value)`.
+	pub fn datum(&self) -> Op {
+		let (kind, irritant) = match *self {
+			Error::Undefined(op) => ("undefined", Some(op)),
+			Error::CanNotApply(op) => ("can_not_apply", Some(op)),
+			Error::RequireLong(op) => ("require_long", Some(op)),
+			Error::RequireNumber(op) => ("require_number", Some(op)),
+			Error::RequireExact(op) => ("require_exact", Some(op)),
+			Error::RequireSymbol(op) => ("require_symbol", Some(op)),
+			Error::RequirePair(op) => ("require_pair", Some(op)),
+			Error::RequireExpr(op) => ("require_expr", Some(op)),
+			Error::TooFewArgs => ("too_few_args", None),
+			Error::BadSyntax(op) => ("bad_syntax", Some(op)),
+			Error::StackOverflow => ("stack_overflow", None),
+			Error::DivisionByZero => ("division_by_zero", None),
+			Error::Overflow => ("overflow", None),
+			Error::Raise(op) => return op,
+			Error::Resume(_, value) => ("resume", Some(value)),
+		};
+		let rest = irritant.map_or(nil(), |op| cons(op, nil()));
+		cons(intern(kind.into()), rest)
+	}
+}
+
+/// State of the evaluator, also what is left to do after a primitive or a
+/// special form returned.
+pub enum Tail {
+	Return(Op),
+	// the expression to evaluate and its environment
+	Eval(Op, Op),
+	// evaluates the expression, then resumes the frame with its value
+	Then(Frame, Op, Op),
+}
+
+/// A pending step of an evaluation, waiting for the value of a
+/// subexpression.
+#[derive(Clone)]
+pub enum Frame {
+	// the head of `form` is being evaluated
+	Head { form: Op, env: Op },
+	// `done` arguments of `fun` are evaluated, `rest` are left
+	Args { fun: Op, done: Vec<Op>, rest: Op, env: Op },
+	// the result of a macro expander is evaluated in `env`
+	Expand { env: Op },
+	If { then: Op, otherwise: Op, env: Op },
+	// `body` is evaluated if the test of its clause holds
+	Cond { body: Op, clauses: Op, env: Op },
+	And { rest: Op, env: Op },
+	Or { rest: Op, env: Op },
+	Body { rest: Op, env: Op },
+	// `pending` bindings are reversed, `name` is bound to the value
+	Let { name: Op, pending: Vec<(Op, Op)>, scope: Op, env: Op, body: Op, sequential: bool },
+	// the value is stored into `pair`
+	Letrec { pair: Op, pending: Vec<(Op, Op)>, scope: Op, body: Op },
+	// `name` is bound in the scope `env` starts with
+	Define { name: Op, env: Op },
+	// the nearest binding of `name` in `env` is assigned
+	Set { name: Op, env: Op },
+	// `handler` is evaluated with `name` bound to an error raised below
+	Try { name: Op, handler: Op, env: Op },
+}
+
+impl Frame {
+	pub fn trace(&self, roots: &mut Vec<Op>) {
+		match self {
+			Frame::Head { form, env } => roots.extend([*form, *env]),
+			Frame::Args { fun, done, rest, env } => {
+				roots.extend([*fun, *rest, *env]);
+				roots.extend(done);
+			}
+			Frame::Expand { env } => roots.push(*env),
+			Frame::If { then, otherwise, env } => roots.extend([*then, *otherwise, *env]),
+			Frame::Cond { body, clauses, env } => roots.extend([*body, *clauses, *env]),
+			Frame::And { rest, env } | Frame::Or { rest, env } | Frame::Body { rest, env } => {
+				roots.extend([*rest, *env]);
+			}
+			Frame::Let { name, pending, scope, env, body, .. } => {
+				roots.extend([*name, *scope, *env, *body]);
+				pending.iter().for_each(|&(name, init)| roots.extend([name, init]));
+			}
+			Frame::Letrec { pair, pending, scope, body } => {
+				roots.extend([*pair, *scope, *body]);
+				pending.iter().for_each(|&(pair, init)| roots.extend([pair, init]));
 			}
-			Object::Pair { head, tail } => {
-				let head = eval(head.into(), env)?;
-				let (should_apply, should_eval_tail) = match head.as_ref() {
-					Some(Object::Subr { is_fixed, .. }) => {
-						(true, !*is_fixed)
+			Frame::Define { name, env } | Frame::Set { name, env } => roots.extend([*name, *env]),
+			Frame::Try { name, handler, env } => roots.extend([*name, *handler, *env]),
+		}
+	}
+
+	fn resume(self, value: Op) -> Result<Tail, Error> {
+		match self {
+			Frame::Head { form, env } => {
+				let fun = value;
+				match fun.get() {
+					Some(Object::Macro { expander }) => {
+						push(Frame::Expand { env })?;
+						apply_tail(expander, cdr(form), env)
+					}
+					Some(Object::Syntax { rules, env: rules_env }) => {
+						Ok(Tail::Eval(syntax::expand(rules, rules_env, form)?, env))
 					}
-					Some(Object::Expr { .. }) => {
-						(true, true)
+					Some(Object::Subr { is_fixed: true, .. }) => {
+						apply_tail(fun, cdr(form), env)
 					}
 					_ => {
-						(false, true)
+						evargs(fun, Vec::new(), cdr(form), env)
 					}
-				};
-				let tail = if should_eval_tail {
-					evlis(tail.into(), env)?
-				} else {
-					tail.into()
-				};
-				if should_apply {
-					apply(head, tail, env)
-				} else {
-					Ok(cons(head, tail))
 				}
 			}
-			_ => {
-				Ok(op)
+			Frame::Args { fun, mut done, rest, env } => {
+				done.push(value);
+				evargs(fun, done, rest, env)
+			}
+			Frame::Expand { env } => {
+				Ok(Tail::Eval(value, env))
+			}
+			Frame::If { then, otherwise, env } => {
+				Ok(Tail::Eval(if value.is_true() { then } else { otherwise }, env))
+			}
+			Frame::Cond { body, clauses, env } => {
+				if !value.is_true() {
+					return evcond(clauses, env)
+				}
+				if body.is_null() {
+					return Ok(Tail::Return(value))
+				}
+				evbody(body, env)
+			}
+			Frame::And { rest, env } => {
+				if !value.is_true() {
+					return Ok(Tail::Return(value))
+				}
+				evand(rest, env)
+			}
+			Frame::Or { rest, env } => {
+				if value.is_true() {
+					return Ok(Tail::Return(value))
+				}
+				evor(rest, env)
+			}
+			Frame::Body { rest, env } => {
+				evbody(rest, env)
+			}
+			Frame::Let { name, pending, scope, env, body, sequential } => {
+				let scope = cons(cons(name, value), scope);
+				evlet(pending, scope, env, body, sequential)
+			}
+			Frame::Letrec { pair, pending, scope, body } => {
+				pair.set_tail_unchecked(value);
+				evletrec(pending, scope, body)
+			}
+			Frame::Define { name, env } => {
+				define(name, value, if env.is_pair() { env } else { globals() });
+				Ok(Tail::Return(value))
+			}
+			Frame::Set { name, env } => {
+				binding(name, env)?.set_tail_unchecked(value);
+				Ok(Tail::Return(value))
+			}
+			Frame::Try { .. } => {
+				Ok(Tail::Return(value))
 			}
 		}
 	}
 }
 
-fn apply(fun: Op, args: Op, env: Op) -> Result<Op, Error> {
+// evaluates the next argument, or applies `fun` once they are all done
+fn evargs(fun: Op, done: Vec<Op>, rest: Op, env: Op) -> Result<Tail, Error> {
+	if !rest.is_null() {
+		let arg = car(rest);
+		return Ok(Tail::Then(Frame::Args { fun, done, rest: cdr(rest), env }, arg, env))
+	}
+	let args = done.into_iter().rev().fold(nil(), |tail, head| cons(head, tail));
+	if fun.is_subr() || fun.is_expr() || fun.is_cont() {
+		apply_tail(fun, args, env)
+	} else {
+		Ok(Tail::Return(cons(fun, args)))
+	}
+}
+
+fn push(frame: Frame) -> Result<(), Error> {
+	context(|ctx| {
+		let mut stack = ctx.stack.borrow_mut();
+		if stack.len() >= MAX_DEPTH {
+			return Err(Error::StackOverflow)
+		}
+		stack.push(frame);
+		Ok(())
+	})
+}
+
+// the frames below `base` belong to enclosing evaluations
+fn pop(base: usize) -> Option<Frame> {
+	context(|ctx| {
+		let mut stack = ctx.stack.borrow_mut();
+		if stack.len() > base { stack.pop() } else { None }
+	})
+}
+
+/// An active run of the machine, the frames above `base` are its own.
+#[derive(Clone, Copy)]
+pub struct Run {
+	serial: usize,
+	base: usize,
+}
+
+pub fn eval(op: Op, env: Op) -> Result<Op, Error> {
+	run(|| Ok(Tail::Eval(op, env)))
+}
+
+// the evaluator is a machine with an explicit control stack, it only nests on
+// the native stack when primitives call back into it.
+fn run(start: impl FnOnce() -> Result<Tail, Error>) -> Result<Op, Error> {
+	let run = context(|ctx| {
+		let mut runs = ctx.runs.borrow_mut();
+		if runs.len() >= MAX_NESTING {
+			return Err(Error::StackOverflow)
+		}
+		let run = Run { serial: ctx.serial.get(), base: ctx.stack.borrow().len() };
+		ctx.serial.set(run.serial + 1);
+		runs.push(run);
+		Ok(run)
+	})?;
+	let result = run_from(run, start());
+	context(|ctx| {
+		ctx.runs.borrow_mut().pop();
+		ctx.stack.borrow_mut().truncate(run.base);
+	});
+	result
+}
+
+fn run_from(run: Run, start: Result<Tail, Error>) -> Result<Op, Error> {
+	let roots = Roots::open();
+	let mut state = start;
+	loop {
+		state = match state {
+			Ok(Tail::Return(value)) => match pop(run.base) {
+				Some(frame) => frame.resume(value),
+				None => return Ok(value),
+			},
+			Ok(Tail::Eval(op, env)) => {
+				roots.clear();
+				roots.push(op);
+				roots.push(env);
+				gc::safepoint();
+				step(op, env)
+			}
+			Ok(Tail::Then(frame, op, env)) => {
+				push(frame).map(|()| Tail::Eval(op, env))
+			}
+			Err(Error::Resume(cont, value)) if resumes_in(cont, run) => {
+				reinstate(cont, run.base).map(|()| Tail::Return(value))
+			}
+			Err(error @ Error::Resume(..)) => {
+				return Err(error)
+			}
+			Err(error) => {
+				Ok(catch(run, error)?)
+			}
+		}
+	}
+}
+
+// unwinds the run to its innermost `try`, the error goes on to the enclosing
+// runs if there is none.
+fn catch(run: Run, error: Error) -> Result<Tail, Error> {
+	let frame = context(|ctx| {
+		let mut stack = ctx.stack.borrow_mut();
+		let at = stack[run.base..].iter().rposition(|frame| matches!(frame, Frame::Try { .. }))?;
+		stack.truncate(run.base + at + 1);
+		stack.pop()
+	});
+	let Some(Frame::Try { name, handler, env }) = frame else {
+		return Err(error)
+	};
+	let env = cons(cons(name, error.datum()), env);
+	evbody(handler, env)
+}
+
+// a continuation resumes in the run that captured it if it is still active,
+// otherwise in the innermost one.
+fn resumes_in(cont: Op, run: Run) -> bool {
+	let Some(Object::Cont { run: serial, .. }) = cont.get() else {
+		unreachable!()
+	};
+	serial == run.serial || context(|ctx| ctx.runs.borrow().iter().all(|run| run.serial != serial))
+}
+
+// replaces the frames of the run by the captured ones
+fn reinstate(cont: Op, base: usize) -> Result<(), Error> {
+	let Some(Object::Cont { frames, .. }) = cont.get() else {
+		unreachable!()
+	};
+	context(|ctx| {
+		let mut stack = ctx.stack.borrow_mut();
+		if base + frames.len() > MAX_DEPTH {
+			return Err(Error::StackOverflow)
+		}
+		stack.truncate(base);
+		stack.extend(frames.iter().cloned());
+		Ok(())
+	})
+}
+
+// evaluates `op` up to its first subexpression
+fn step(op: Op, env: Op) -> Result<Tail, Error> {
+	#[cfg(feature = "trace")]
+	println!("EVAL {:?} IN {:?}", op, env);
+	match op.get() {
+		None => {
+			Ok(Tail::Return(nil()))
+		}
+		Some(Object::Symbol { .. }) => {
+			Ok(Tail::Return(cdr(binding(op, env)?)))
+		}
+		Some(Object::Pair { head, .. }) => {
+			Ok(Tail::Then(Frame::Head { form: op, env }, head, env))
+		}
+		_ => {
+			Ok(Tail::Return(op))
+		}
+	}
+}
+
+// the operands stay rooted while primitives run
+fn apply_tail(fun: Op, args: Op, env: Op) -> Result<Tail, Error> {
+	#[cfg(feature = "trace")]
 	println!("APPLY {:?} TO {:?} IN {:?}", fun, args, env);
 	if fun.is_null() {
 		return Err(Error::CanNotApply(fun))
 	}
-	match fun.as_ref_unchecked() {
-		Object::Subr { imp, .. } => {
-			imp(args, env)
+	let roots = Roots::open();
+	roots.push(fun);
+	roots.push(args);
+	roots.push(env);
+	match fun.get_unchecked() {
+		Object::Subr { imp: Imp::Prim(imp), .. } => {
+			imp(args, env).map(Tail::Return)
+		}
+		Object::Subr { imp: Imp::Form(form), .. } => {
+			form(args, env)
+		}
+		Object::Expr { def, env: def_env } => {
+			let env = pairlis(def.get_head_unchecked(), args, def_env)?;
+			// a call always gets its own scope for `define`
+			evbody(def.get_tail_unchecked(), own_scope(env, def_env))
 		}
-		Object::Expr { def, env } => {
-			let def: Op = def.into();
-			let env: Op = env.into();
-			let env = pairlis(def.get_head_unchecked(), args, env)?;
-			eval(def.get_tail_unchecked(), env)
+		Object::Cont { .. } => {
+			Err(Error::Resume(fun, car(args)))
 		}
 		_ => {
 			Err(Error::CanNotApply(fun))
@@ -78,16 +392,27 @@ fn apply(fun: Op, args: Op, env: Op) -> Result<Op, Error> {
 	}
 }
 
+fn apply(fun: Op, args: Op, env: Op) -> Result<Op, Error> {
+	run(|| apply_tail(fun, args, env))
+}
+
+pub fn call(fun: Op, args: Op) -> Result<Op, Error> {
+	let roots = Roots::open();
+	roots.push(fun);
+	roots.push(args);
+	apply(fun, args, globals())
+}
+
 fn car(op: Op) -> Op {
-	match op.as_ref() {
-		Some(Object::Pair { head, .. }) => Op(*head),
+	match op.get() {
+		Some(Object::Pair { head, .. }) => head,
 		_ => nil()
 	}
 }
 
 fn cdr(op: Op) -> Op {
-	match op.as_ref() {
-		Some(Object::Pair { tail, .. }) => Op(*tail),
+	match op.get() {
+		Some(Object::Pair { tail, .. }) => tail,
 		_ => nil()
 	}
 }
@@ -95,10 +420,18 @@ fn cdr(op: Op) -> Op {
 fn caar(op: Op) -> Op { car(car(op)) }
 fn cadr(op: Op) -> Op { car(cdr(op)) }
 
+// binds `name` first in the scope starting at `env`. Closures hold on to
+// the first cell of their scope, so it is kept and its binding updated or
+// moved down.
 pub fn define(name: Op, value: Op, env: Op) -> Op {
+	let head = car(env);
+	if car(head) == name {
+		head.set_tail_unchecked(value);
+		return head
+	}
 	let pair = cons(name, value);
-	let env_tail = cons(pair, cdr(env));
-	env.set_tail_unchecked(env_tail);
+	env.set_tail_unchecked(cons(head, cdr(env)));
+	env.set_head_unchecked(pair);
 	pair
 }
 
@@ -122,24 +455,41 @@ fn pairlis(mut names: Op, mut values: Op, mut env: Op) -> Result<Op, Error> {
 	Ok(env)
 }
 
-fn assoc(key: Op, env: Op) -> Op {
-	if caar(env) == key {
-		return car(env)
+// the pair binding `symbol`, an alias not bound by its expansion refers to the
+// original symbol in the macro's environment.
+fn binding(mut symbol: Op, mut env: Op) -> Result<Op, Error> {
+	loop {
+		let pair = assoc(symbol, env);
+		if !pair.is_null() {
+			return Ok(pair)
+		}
+		match symbol.get() {
+			Some(Object::Symbol { alias: Some((base, alias_env)), .. }) => (symbol, env) = (base, alias_env),
+			_ => return Err(Error::Undefined(symbol)),
+		}
 	}
-	let tail = cdr(env);
-	if tail.is_null() {
-		return nil()
+}
+
+pub fn assoc(key: Op, mut env: Op) -> Op {
+	while !env.is_null() {
+		if caar(env) == key {
+			return car(env)
+		}
+		env = cdr(env);
 	}
-	assoc(key, tail)
+	nil()
 }
 
-fn evlis(op: Op, env: Op) -> Result<Op, Error> {
-	if op.is_null() {
-		return Ok(nil())
+// evaluates the expressions of `body` in order, leaving the last one in tail
+// position.
+fn evbody(body: Op, env: Op) -> Result<Tail, Error> {
+	if body.is_null() {
+		return Ok(Tail::Return(nil()))
 	}
-	let head = eval(car(op), env)?;
-	let tail = evlis(cdr(op), env)?;
-	Ok(cons(head, tail))
+	if !cdr(body).is_pair() {
+		return Ok(Tail::Eval(car(body), env))
+	}
+	Ok(Tail::Then(Frame::Body { rest: cdr(body), env }, car(body), env))
 }
 
 macro_rules! check_args {
@@ -159,35 +509,49 @@ macro_rules! check_args {
 	};
 }
 
-pub fn subr_define(args: Op, env: Op) -> Result<Op, Error> {
+// binds `name` in the innermost scope, which is the global one at the top level
+pub fn form_define(args: Op, env: Op) -> Result<Tail, Error> {
 	let name = car(args);
 	if !name.is_symbol() {
 		return Err(Error::RequireSymbol(name))
 	}
-	let value = eval(cadr(args), env)?;
-	define(name, value, unsafe { GLOBALS });
-	Ok(value)
+	Ok(Tail::Then(Frame::Define { name, env }, cadr(args), env))
+}
+
+// `(set name value)` assigns the nearest binding of `name`
+pub fn form_set(args: Op, env: Op) -> Result<Tail, Error> {
+	check_args! {
+		args,
+		let name, is_symbol, RequireSymbol
+		let value
+	}
+	Ok(Tail::Then(Frame::Set { name, env }, value, env))
 }
 
+// the body is one or more expressions evaluated in order
 pub fn subr_lambda(args: Op, env: Op) -> Result<Op, Error> {
 	check_args! {
 		args,
 		let symbols
-		let body
+		let _body
 	}
-	Ok(Op::expr(cons(symbols, body), env))
+	Ok(Op::expr(cons(symbols, args), env))
 }
 
 // lambda that omit the arg list
 pub fn subr_lambda_lambda(args: Op, env: Op) -> Result<Op, Error> {
 	check_args! {
 		args,
-		let body
+		let _body
 	}
-	Ok(Op::expr(cons(nil(), body), env))
+	Ok(Op::expr(cons(nil(), args), env))
+}
+
+pub fn form_begin(args: Op, env: Op) -> Result<Tail, Error> {
+	evbody(args, env)
 }
 
-pub fn subr_apply(args: Op, env: Op) -> Result<Op, Error> {
+pub fn form_apply(args: Op, env: Op) -> Result<Tail, Error> {
 	let fun = car(args);
 	let args = cdr(args);
 	let fun_args = car(args);
@@ -196,67 +560,328 @@ pub fn subr_apply(args: Op, env: Op) -> Result<Op, Error> {
 	if ctx.is_null() {
 		ctx = env;
 	}
-	apply(fun, fun_args, ctx)
+	apply_tail(fun, fun_args, ctx)
 }
 
-pub fn subr_set_scope(args: Op, env: Op) -> Result<Op, Error> {
-	subr_define(args, env)
+// `(call_cc f)` applies `f` to the continuation of the `call_cc` form, a
+// continuation may be resumed any number of times.
+pub fn form_call_cc(args: Op, env: Op) -> Result<Tail, Error> {
+	check_args! {
+		args,
+		let fun
+	}
+	let cont = context(|ctx| {
+		let run = *ctx.runs.borrow().last().expect("call_cc outside of a run");
+		let frames = ctx.stack.borrow()[run.base..].into();
+		Op::cont(frames, run.serial)
+	});
+	apply_tail(fun, cons(cont, nil()), env)
 }
 
-pub fn subr_get_scope(args: Op, env: Op) -> Result<Op, Error> {
+pub fn subr_raise(args: Op, _env: Op) -> Result<Op, Error> {
+	Err(Error::Raise(car(args)))
+}
+
+// `(try expr (catch name handler ...))` evaluates the handler with `name`
+// bound to the error if evaluating `expr` fails.
+pub fn form_try(args: Op, env: Op) -> Result<Tail, Error> {
 	check_args! {
 		args,
-		let name
-	};
-	if name.is_long() {
-		let n = name.get_long_unchecked();
-		let mut env = env;
-		while !env.is_null() {
-			let pair = car(env);
-			let key = car(pair);
-			if key.is_long() && key.get_long_unchecked() == n {
-				return Ok(cdr(pair))
+		let expr
+		let clause, is_pair, RequirePair
+	}
+	if syntax::base(car(clause)) != intern("catch".into()) {
+		return Err(Error::BadSyntax(clause))
+	}
+	let name = cadr(clause);
+	if !name.is_symbol() {
+		return Err(Error::RequireSymbol(name))
+	}
+	let handler = cdr(cdr(clause));
+	Ok(Tail::Then(Frame::Try { name, handler, env }, expr, env))
+}
+
+pub fn subr_quote(args: Op, _env: Op) -> Result<Op, Error> {
+	check_args! {
+		args,
+		let datum
+	}
+	Ok(syntax::strip(datum))
+}
+
+pub fn subr_quasiquote(args: Op, env: Op) -> Result<Op, Error> {
+	check_args! {
+		args,
+		let template
+	}
+	quasi(template, env, 0)
+}
+
+// only unquotes at `depth` 0 are evaluated, nested quasiquotes increase it
+fn quasi(template: Op, env: Op, depth: usize) -> Result<Op, Error> {
+	if !template.is_pair() {
+		return Ok(syntax::strip(template))
+	}
+	let head = syntax::base(template.get_head_unchecked());
+	let unquote = intern("unquote".into());
+	let unquote_splicing = intern("unquote_splicing".into());
+	let quasiquote = intern("quasiquote".into());
+	if head == unquote || head == unquote_splicing {
+		if depth == 0 {
+			return eval(cadr(template), env)
+		}
+		let datum = quasi(cadr(template), env, depth - 1)?;
+		return Ok(cons(head, cons(datum, nil())))
+	}
+	if head == quasiquote {
+		let datum = quasi(cadr(template), env, depth + 1)?;
+		return Ok(cons(head, cons(datum, nil())))
+	}
+	let roots = Roots::open();
+	let mut items = Vec::new();
+	let mut list = template;
+	while list.is_pair() {
+		let item = list.get_head_unchecked();
+		if depth == 0 && syntax::base(car(item)) == unquote_splicing && item.is_pair() {
+			let spliced = roots.push(eval(cadr(item), env)?);
+			if !spliced.is_null() && !spliced.is_pair() {
+				return Err(Error::RequirePair(spliced))
 			}
-			env = cdr(env);
+			items.extend(spliced.iter());
+		} else {
+			items.push(roots.push(quasi(item, env, depth)?));
 		}
+		list = list.get_tail_unchecked();
 	}
-	Ok(name)
+	Ok(items.into_iter().rev().fold(list, |tail, head| cons(head, tail)))
 }
 
-pub fn subr_add(args: Op, _env: Op) -> Result<Op, Error> {
+pub fn form_if(args: Op, env: Op) -> Result<Tail, Error> {
 	check_args! {
 		args,
-		let lhs, is_long, RequireLong
-		let rhs, is_long, RequireLong
-	};
-	Ok(Op::long(lhs.get_long_unchecked() + rhs.get_long_unchecked()))
+		let test
+		let then
+	}
+	let otherwise = car(cdr(args));
+	Ok(Tail::Then(Frame::If { then, otherwise, env }, test, env))
 }
 
-pub fn subr_subtract(args: Op, _env: Op) -> Result<Op, Error> {
+// each clause is a test followed by the expressions evaluated when it holds,
+// a clause without expressions yields the value of its test.
+pub fn form_cond(args: Op, env: Op) -> Result<Tail, Error> {
+	evcond(args, env)
+}
+
+fn evcond(clauses: Op, env: Op) -> Result<Tail, Error> {
+	if !clauses.is_pair() {
+		return Ok(Tail::Return(nil()))
+	}
+	let clause = clauses.get_head_unchecked();
+	if !clause.is_pair() {
+		return Err(Error::RequirePair(clause))
+	}
+	let frame = Frame::Cond { body: clause.get_tail_unchecked(), clauses: clauses.get_tail_unchecked(), env };
+	Ok(Tail::Then(frame, clause.get_head_unchecked(), env))
+}
+
+// the last operand of `and` and `or` is in tail position
+pub fn form_and(args: Op, env: Op) -> Result<Tail, Error> {
+	if !args.is_pair() {
+		return Ok(Tail::Return(truth(true)))
+	}
+	evand(args, env)
+}
+
+fn evand(args: Op, env: Op) -> Result<Tail, Error> {
+	if !cdr(args).is_pair() {
+		return Ok(Tail::Eval(car(args), env))
+	}
+	Ok(Tail::Then(Frame::And { rest: cdr(args), env }, car(args), env))
+}
+
+pub fn form_or(args: Op, env: Op) -> Result<Tail, Error> {
+	if !args.is_pair() {
+		return Ok(Tail::Return(nil()))
+	}
+	evor(args, env)
+}
+
+fn evor(args: Op, env: Op) -> Result<Tail, Error> {
+	if !cdr(args).is_pair() {
+		return Ok(Tail::Eval(car(args), env))
+	}
+	Ok(Tail::Then(Frame::Or { rest: cdr(args), env }, car(args), env))
+}
+
+pub fn subr_not(args: Op, _env: Op) -> Result<Op, Error> {
+	Ok(truth(!car(args).is_true()))
+}
+
+// a binding is a `(name init)` list, `nil` entries are skipped since the
+// empty binding list `()` reads as `(nil)`.
+fn bindings(mut list: Op) -> Result<Vec<(Op, Op)>, Error> {
+	let mut bindings = Vec::new();
+	while list.is_pair() {
+		let binding = list.get_head_unchecked();
+		list = list.get_tail_unchecked();
+		if binding.is_null() {
+			continue
+		}
+		if !binding.is_pair() {
+			return Err(Error::RequirePair(binding))
+		}
+		let name = binding.get_head_unchecked();
+		if !name.is_symbol() {
+			return Err(Error::RequireSymbol(name))
+		}
+		bindings.push((name, cadr(binding)))
+	}
+	Ok(bindings)
+}
+
+// `scope` unless it is still `env`, i.e. nothing was bound, then an empty
+// scope on top of `env`
+fn own_scope(scope: Op, env: Op) -> Op {
+	if scope == env {
+		return cons(cons(nil(), nil()), env)
+	}
+	scope
+}
+
+// `sequential` evaluates each init in the scope of the previous bindings
+fn bind_let(args: Op, env: Op, sequential: bool) -> Result<Tail, Error> {
 	check_args! {
 		args,
-		let lhs, is_long, RequireLong
-		let rhs, is_long, RequireLong
+		let list
+	}
+	let mut pending = bindings(list)?;
+	pending.reverse();
+	evlet(pending, env, env, args.get_tail_unchecked(), sequential)
+}
+
+fn evlet(mut pending: Vec<(Op, Op)>, scope: Op, env: Op, body: Op, sequential: bool) -> Result<Tail, Error> {
+	let Some((name, init)) = pending.pop() else {
+		return evbody(body, own_scope(scope, env))
 	};
-	Ok(Op::long(lhs.get_long_unchecked() - rhs.get_long_unchecked()))
+	let init_env = if sequential { scope } else { env };
+	Ok(Tail::Then(Frame::Let { name, pending, scope, env, body, sequential }, init, init_env))
+}
+
+pub fn form_let(args: Op, env: Op) -> Result<Tail, Error> {
+	bind_let(args, env, false)
 }
 
-pub fn subr_mul(args: Op, _env: Op) -> Result<Op, Error> {
+pub fn form_let_star(args: Op, env: Op) -> Result<Tail, Error> {
+	bind_let(args, env, true)
+}
+
+// every init is evaluated in the scope of all the bindings, so that they
+// can refer to each other.
+pub fn form_letrec(args: Op, env: Op) -> Result<Tail, Error> {
 	check_args! {
 		args,
-		let lhs, is_long, RequireLong
-		let rhs, is_long, RequireLong
+		let list
+	}
+	let mut scope = env;
+	let mut pending = Vec::new();
+	for (name, init) in bindings(list)? {
+		let pair = cons(name, nil());
+		scope = cons(pair, scope);
+		pending.push((pair, init));
+	}
+	pending.reverse();
+	evletrec(pending, own_scope(scope, env), args.get_tail_unchecked())
+}
+
+fn evletrec(mut pending: Vec<(Op, Op)>, scope: Op, body: Op) -> Result<Tail, Error> {
+	let Some((pair, init)) = pending.pop() else {
+		return evbody(body, scope)
 	};
-	Ok(Op::long(lhs.get_long_unchecked() * rhs.get_long_unchecked()))
+	Ok(Tail::Then(Frame::Letrec { pair, pending, scope, body }, init, scope))
+}
+
+pub fn subr_macro(args: Op, env: Op) -> Result<Op, Error> {
+	let expander = subr_lambda(args, env)?;
+	Ok(Op::mac(expander))
+}
+
+pub fn subr_defmacro(args: Op, env: Op) -> Result<Op, Error> {
+	check_args! {
+		args,
+		let name, is_symbol, RequireSymbol
+	}
+	let mac = subr_macro(args.get_tail_unchecked(), env)?;
+	// bound in the current scope like with `define`
+	define(name, mac, if env.is_pair() { env } else { globals() });
+	Ok(mac)
+}
+
+pub fn subr_syntax_rules(args: Op, env: Op) -> Result<Op, Error> {
+	Ok(Op::syntax(args, env))
 }
 
-pub fn subr_div(args: Op, _env: Op) -> Result<Op, Error> {
+// used by `macroexpand`, the evaluator expands macros on its own stack
+fn expand(mac: Op, form: Op, env: Op) -> Result<Op, Error> {
+	match mac.get_unchecked() {
+		Object::Macro { expander } => apply(expander, cdr(form), env),
+		Object::Syntax { rules, env } => syntax::expand(rules, env, form),
+		_ => unreachable!()
+	}
+}
+
+// expands `form` until its head no longer names a macro
+pub fn subr_macroexpand(args: Op, env: Op) -> Result<Op, Error> {
 	check_args! {
 		args,
-		let lhs, is_long, RequireLong
-		let rhs, is_long, RequireLong
+		let form
+	}
+	let roots = Roots::open();
+	let mut form = form;
+	while car(form).is_symbol() {
+		let mac = match eval(car(form), env) {
+			Ok(mac) if mac.is_macro() => mac,
+			_ => break
+		};
+		form = roots.push(expand(mac, form, env)?);
+	}
+	Ok(form)
+}
+
+pub fn form_set_scope(args: Op, env: Op) -> Result<Tail, Error> {
+	form_define(args, env)
+}
+
+pub fn subr_get_scope(args: Op, env: Op) -> Result<Op, Error> {
+	check_args! {
+		args,
+		let name
 	};
-	Ok(Op::long(lhs.get_long_unchecked() / rhs.get_long_unchecked()))
+	if name.is_long() {
+		let n = name.get_long_unchecked();
+		let mut env = env;
+		while !env.is_null() {
+			let pair = car(env);
+			let key = car(pair);
+			if key.is_long() && key.get_long_unchecked() == n {
+				return Ok(cdr(pair))
+			}
+			env = cdr(env);
+		}
+	}
+	Ok(name)
+}
+
+// `nil` is false, the `true` symbol is the canonical true value.
+pub fn truth(b: bool) -> Op {
+	if b { intern("true".into()) } else { nil() }
+}
+
+pub fn subr_eq(args: Op, _env: Op) -> Result<Op, Error> {
+	let values: Vec<Op> = args.iter().collect();
+	if values.len() < 2 {
+		return Err(Error::TooFewArgs)
+	}
+	Ok(truth(values.windows(2).all(|pair| num::eqv(pair[0], pair[1]))))
 }
 
 pub fn subr_new_list(_args: Op, _env: Op) -> Result<Op, Error> {
@@ -363,6 +988,8 @@ pub fn subr_list_map(args: Op, _env: Op) -> Result<Op, Error> {
 	if list.get_head_unchecked().is_null() {
 		return Ok(new_list)
 	}
+	let roots = Roots::open();
+	roots.push(new_list);
 	let mut tail = list;
 	let mut new_tail = new_list;
 	loop {
@@ -370,8 +997,9 @@ pub fn subr_list_map(args: Op, _env: Op) -> Result<Op, Error> {
 		// call closure with modified context then restore it.
 		let original_env = fun.get_env_unchecked();
 		fun.set_env_unchecked(cons(cons(Op::long(0), elem), original_env));
-		let new_elem = apply(fun, cons(elem, nil()), nil())?;
+		let new_elem = apply(fun, cons(elem, nil()), nil());
 		fun.set_env_unchecked(original_env);
+		let new_elem = new_elem?;
 		new_tail.set_head_unchecked(new_elem);
 		tail = tail.get_tail_unchecked();
 		if tail.is_null() {
@@ -386,3 +1014,123 @@ pub fn subr_list_map(args: Op, _env: Op) -> Result<Op, Error> {
 	}
 	Ok(new_list)
 }
+
+pub fn subr_gc(_args: Op, _env: Op) -> Result<Op, Error> {
+	Ok(Op::long(gc::collect() as isize))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{Interpreter, BaseReader, Reader as _};
+
+	// the value of the last expression of `src`
+	pub(super) fn run(src: &str) -> String {
+		Interpreter::new().enter(|| {
+			let mut reader = BaseReader::new(src);
+			let mut value = String::new();
+			while let Ok(op) = reader.read() {
+				value = format!("{:?}", crate::eval(op).unwrap());
+			}
+			value
+		})
+	}
+
+	#[test]
+	fn define_rebinds_first_binding_of_scope() {
+		assert_eq!(run("((lambda (x) (define x 5) x) 1)"), "5");
+		assert_eq!(run("(let ((y 1)) (define y 2) y)"), "2");
+		assert_eq!(run("((lambda (a b) (define a 9) a) 1 2)"), "9");
+		assert_eq!(run("((lambda (a b) (define b 9) b) 1 2)"), "9");
+		assert_eq!(run("(define x 1) (define x 2) x"), "2");
+	}
+
+	#[test]
+	fn splices_only_lists() {
+		assert_eq!(run("(define x '(2 3)) `(1 ~@x 4)"), "(1 2 3 4)");
+		assert_eq!(run("`(1 ~@nil)"), "(1)");
+		assert_eq!(run("(try `(1 ~@3) (catch e e))"), "(require_pair 3)");
+	}
+
+	#[test]
+	fn let_body_has_its_own_scope() {
+		assert_eq!(run("(let () (define z 1) z)"), "1");
+		assert_eq!(run("(let () (define z 1)) (try z (catch e e))"), "(undefined z)");
+		assert_eq!(run("(letrec () (define z 1)) (try z (catch e e))"), "(undefined z)");
+		assert_eq!(run("(define z 0) (let_star () (define z 1)) z"), "0");
+	}
+
+	#[test]
+	fn defmacro_is_local_to_the_scope() {
+		assert_eq!(run("(let ((a 1)) (defmacro m (x) x) (m 5))"), "5");
+		assert_eq!(run("(define m 0) (let ((a 1)) (defmacro m (x) x)) m"), "0");
+		assert_eq!(run("(defmacro m (x) x) (m 6)"), "6");
+	}
+
+	#[test]
+	fn define_keeps_closure_scope() {
+		assert_eq!(run("((lambda (x) (define q 3) (add x q)) 1)"), "4");
+		assert_eq!(run("(define f (lambda (x) (lambda (z) (define x z) x))) ((f 1) 7)"), "7");
+	}
+
+	#[test]
+	fn continuations_escape() {
+		assert_eq!(run("(add 1 (call_cc (lambda (k) (add 10 (k 2)))))"), "3");
+		let nested = "(define g (lambda (k) (mul 100 (k 5)))) (define f (lambda (k) (g k)))";
+		assert_eq!(run(&format!("{nested} (add 1 (call_cc f))")), "6");
+	}
+
+	#[test]
+	fn continuations_escape_through_primitives() {
+		let escape = "(call_cc (lambda (k) (list_map '(1 2 3) (lambda (x) (if (eq x 2) (k 99) x)))))";
+		assert_eq!(run(escape), "99");
+		assert_eq!(run(&format!("(add 1 (try {escape} (catch e 0)))")), "100");
+	}
+
+	#[test]
+	fn continuations_reenter() {
+		// the rest of the `let` runs again for each value given to `saved`
+		let count = "(define saved 0) (define count 0)
+			(define counted (lambda (x) (let ((v (call_cc (lambda (k) (set saved k) 0))))
+				(set count (add count 1))
+				(if (lt v 2) (saved (add v 1)) `(~v ~count)))))";
+		assert_eq!(run(&format!("{count} (counted 0)")), "(2 3)");
+	}
+
+	#[test]
+	fn continuations_outlive_their_run() {
+		let save = "(define saved 0) (define count 0)
+			(define r (let ((v (call_cc (lambda (k) (set saved k) 0))))
+				(set count (add count 1))
+				`(~v ~count)))";
+		assert_eq!(run(&format!("{save} r")), "(0 1)");
+		assert_eq!(run(&format!("{save} (saved 7) (saved 8) r")), "(8 3)");
+		// only the frames of the evaluation run by the primitive are kept
+		let inner = "(define saved 0)
+			(list_map '(1 2 3) (lambda (x) (call_cc (lambda (k) (if (eq x 2) (set saved k)) x))))";
+		assert_eq!(run(&format!("{inner} (saved 20)")), "20");
+	}
+
+	#[test]
+	fn errors_are_caught_as_data() {
+		assert_eq!(run("(try x (catch e e))"), "(undefined x)");
+		assert_eq!(run("(try (div 1 0) (catch e e))"), "(division_by_zero)");
+		assert_eq!(run("(try (add 1 'a) (catch e e))"), "(require_number a)");
+		assert_eq!(run("(try (raise 42) (catch e e))"), "42");
+		assert_eq!(run("(try (raise '(my error)) (catch e e))"), "(my error)");
+		assert_eq!(run("(try (try (raise 1) (catch e (raise (add e 1)))) (catch e e))"), "2");
+	}
+
+	#[test]
+	fn stack_overflow_is_caught() {
+		let deep = "(define deep (lambda (n) (add 1 (deep n))))";
+		assert_eq!(run(&format!("{deep} (try (deep 0) (catch e e))")), "(stack_overflow)");
+	}
+
+	#[test]
+	fn try_only_catches_while_active() {
+		let guarded = "(define guarded (lambda (x) (try x (catch e 'inner))))";
+		assert_eq!(run(&format!("{guarded} (try (add (guarded 1) (raise 'outer)) (catch e e))")), "outer");
+		let late = "(define late (try (lambda (x) (raise 'late)) (catch e 'caught)))";
+		assert_eq!(run(&format!("{late} (try (late 0) (catch e e))")), "late");
+	}
+}
@@ -0,0 +1,624 @@
+use super::{Error, truth};
+use crate::{Op, Value, BigInt, Ratio};
+use core::cmp::Ordering;
+
+// bound on the results of `pow` and shifts, larger ones overflow
+const MAX_BITS: usize = 1 << 20;
+
+// an integer argument, operations on longs fall back to bignums when they
+// overflow.
+#[derive(Clone)]
+enum Int {
+	Long(isize),
+	Big(BigInt),
+}
+
+impl Int {
+	fn from_op(op: Op) -> Option<Self> {
+		match op.value() {
+			Value::Long(n) => Some(Int::Long(n)),
+			Value::Big(n) => Some(Int::Big(n)),
+			_ => None
+		}
+	}
+
+	// a long when `n` fits one
+	fn norm(n: BigInt) -> Self {
+		match n.to_isize() {
+			Some(n) => Int::Long(n),
+			None => Int::Big(n),
+		}
+	}
+
+	fn into_op(self) -> Op {
+		match self {
+			Int::Long(n) => Op::long(n),
+			Int::Big(n) => Op::big(n),
+		}
+	}
+
+	fn big(&self) -> BigInt {
+		match self {
+			Int::Long(n) => BigInt::from(*n),
+			Int::Big(n) => n.clone(),
+		}
+	}
+
+	fn is_zero(&self) -> bool {
+		matches!(self, Int::Long(0))
+	}
+
+	fn is_negative(&self) -> bool {
+		match self {
+			Int::Long(n) => *n < 0,
+			Int::Big(n) => n.is_negative(),
+		}
+	}
+
+	fn is_odd(&self) -> bool {
+		match self {
+			Int::Long(n) => n & 1 == 1,
+			Int::Big(n) => n.is_odd(),
+		}
+	}
+
+	fn bits(&self) -> usize {
+		match self {
+			Int::Long(n) => (isize::BITS - n.unsigned_abs().leading_zeros()) as usize,
+			Int::Big(n) => n.bits(),
+		}
+	}
+
+	fn abs(self) -> Self {
+		match self {
+			Int::Long(n) => n.checked_abs().map_or_else(|| Int::norm(BigInt::from(n).abs()), Int::Long),
+			Int::Big(n) => Int::Big(n.abs()),
+		}
+	}
+
+	fn float(&self) -> f64 {
+		match self {
+			Int::Long(n) => *n as f64,
+			Int::Big(n) => n.to_f64(),
+		}
+	}
+}
+
+impl PartialEq for Int {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
+}
+
+impl Eq for Int {}
+
+impl Ord for Int {
+	fn cmp(&self, other: &Self) -> Ordering {
+		match (self, other) {
+			(Int::Long(lhs), Int::Long(rhs)) => lhs.cmp(rhs),
+			_ => self.big().cmp(&other.big()),
+		}
+	}
+}
+
+impl PartialOrd for Int {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+// a numeric argument. Integers, fractions and floats form a tower: an
+// operation on exact numbers is exact, it gives a float as soon as one of
+// its operands is a float.
+#[derive(Clone)]
+enum Num {
+	Int(Int),
+	// never an integer
+	Ratio(Ratio),
+	Float(f64),
+}
+
+impl Num {
+	fn from_op(op: Op) -> Option<Self> {
+		match op.value() {
+			Value::Long(n) => Some(Num::Int(Int::Long(n))),
+			Value::Big(n) => Some(Num::Int(Int::Big(n))),
+			Value::Ratio(n) => Some(Num::Ratio(n)),
+			Value::Float(n) => Some(Num::Float(n)),
+			_ => None
+		}
+	}
+
+	fn long(n: isize) -> Self {
+		Num::Int(Int::Long(n))
+	}
+
+	// an integer when `n` is one
+	fn norm(n: Ratio) -> Self {
+		if n.is_integer() {
+			return Num::Int(Int::norm(n.numer().clone()))
+		}
+		Num::Ratio(n)
+	}
+
+	fn into_op(self) -> Op {
+		match self {
+			Num::Int(n) => n.into_op(),
+			Num::Ratio(n) => Op::ratio(n),
+			Num::Float(n) => Op::float(n),
+		}
+	}
+
+	// only for exact numbers
+	fn ratio(&self) -> Ratio {
+		match self {
+			Num::Int(n) => Ratio::from(n.big()),
+			Num::Ratio(n) => n.clone(),
+			Num::Float(_) => unreachable!(),
+		}
+	}
+
+	fn float(&self) -> f64 {
+		match self {
+			Num::Int(n) => n.float(),
+			Num::Ratio(n) => n.to_f64(),
+			Num::Float(n) => *n,
+		}
+	}
+
+	fn is_zero(&self) -> bool {
+		match self {
+			Num::Int(n) => n.is_zero(),
+			Num::Ratio(_) => false,
+			Num::Float(n) => *n == 0.0,
+		}
+	}
+
+	fn is_negative(&self) -> bool {
+		match self {
+			Num::Int(n) => n.is_negative(),
+			Num::Ratio(n) => n.is_negative(),
+			Num::Float(n) => *n < 0.0,
+		}
+	}
+
+	fn is_nan(&self) -> bool {
+		matches!(self, Num::Float(n) if n.is_nan())
+	}
+}
+
+// exact numbers are compared exactly, with floats as floats
+impl PartialEq for Num {
+	fn eq(&self, other: &Self) -> bool {
+		self.partial_cmp(other) == Some(Ordering::Equal)
+	}
+}
+
+impl PartialOrd for Num {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		match (self, other) {
+			(Num::Int(lhs), Num::Int(rhs)) => Some(lhs.cmp(rhs)),
+			(Num::Float(_), _) | (_, Num::Float(_)) => self.float().partial_cmp(&other.float()),
+			_ => Some(self.ratio().cmp(&other.ratio())),
+		}
+	}
+}
+
+/// Numbers are compared by value, other values by identity.
+pub fn eqv(lhs: Op, rhs: Op) -> bool {
+	match (Num::from_op(lhs), Num::from_op(rhs)) {
+		(Some(lhs), Some(rhs)) => lhs == rhs,
+		_ => lhs == rhs
+	}
+}
+
+// the arguments converted by `from`, there must be at least `min` of them
+fn collect<T>(args: Op, min: usize, from: fn(Op) -> Option<T>, error: fn(Op) -> Error) -> Result<Vec<T>, Error> {
+	let items = args.iter()
+		.map(|op| from(op).ok_or(error(op)))
+		.collect::<Result<Vec<_>, _>>()?;
+	if items.len() < min {
+		return Err(Error::TooFewArgs)
+	}
+	Ok(items)
+}
+
+fn ints(args: Op, min: usize) -> Result<Vec<Int>, Error> {
+	collect(args, min, Int::from_op, Error::RequireLong)
+}
+
+fn nums(args: Op, min: usize) -> Result<Vec<Num>, Error> {
+	collect(args, min, Num::from_op, Error::RequireNumber)
+}
+
+// the first two of at least two items
+fn pair<T>(mut items: Vec<T>) -> [T; 2] {
+	items.truncate(2);
+	let rhs = items.pop().unwrap();
+	[items.pop().unwrap(), rhs]
+}
+
+fn divisor(n: &Int) -> Result<&Int, Error> {
+	if n.is_zero() {
+		return Err(Error::DivisionByZero)
+	}
+	Ok(n)
+}
+
+// `long` on longs, `big` on bignums when `long` overflows
+fn binary(lhs: &Int, rhs: &Int, long: fn(isize, isize) -> Option<isize>, big: fn(&BigInt, &BigInt) -> BigInt) -> Int {
+	if let (Int::Long(lhs), Int::Long(rhs)) = (lhs, rhs) {
+		if let Some(n) = long(*lhs, *rhs) {
+			return Int::Long(n)
+		}
+	}
+	Int::norm(big(&lhs.big(), &rhs.big()))
+}
+
+// `int` on integers, `ratio` on other exact numbers and `float` as soon as
+// one operand is a float
+fn arith(
+	lhs: &Num,
+	rhs: &Num,
+	int: fn(&Int, &Int) -> Result<Num, Error>,
+	ratio: fn(&Ratio, &Ratio) -> Option<Ratio>,
+	float: fn(f64, f64) -> f64,
+) -> Result<Num, Error> {
+	match (lhs, rhs) {
+		(Num::Int(lhs), Num::Int(rhs)) => int(lhs, rhs),
+		(Num::Float(_), _) | (_, Num::Float(_)) => Ok(Num::Float(float(lhs.float(), rhs.float()))),
+		_ => ratio(&lhs.ratio(), &rhs.ratio()).map(Num::norm).ok_or(Error::DivisionByZero),
+	}
+}
+
+fn add(lhs: &Num, rhs: &Num) -> Result<Num, Error> {
+	arith(lhs, rhs,
+		|lhs, rhs| Ok(Num::Int(binary(lhs, rhs, isize::checked_add, BigInt::add))),
+		|lhs, rhs| Some(lhs.add(rhs)),
+		|lhs, rhs| lhs + rhs)
+}
+
+fn sub(lhs: &Num, rhs: &Num) -> Result<Num, Error> {
+	arith(lhs, rhs,
+		|lhs, rhs| Ok(Num::Int(binary(lhs, rhs, isize::checked_sub, BigInt::sub))),
+		|lhs, rhs| Some(lhs.sub(rhs)),
+		|lhs, rhs| lhs - rhs)
+}
+
+fn mul(lhs: &Num, rhs: &Num) -> Result<Num, Error> {
+	arith(lhs, rhs,
+		|lhs, rhs| Ok(Num::Int(binary(lhs, rhs, isize::checked_mul, BigInt::mul))),
+		|lhs, rhs| Some(lhs.mul(rhs)),
+		|lhs, rhs| lhs * rhs)
+}
+
+// integers that do not divide give a fraction
+fn div(lhs: &Num, rhs: &Num) -> Result<Num, Error> {
+	arith(lhs, rhs,
+		|lhs, rhs| Ratio::new(lhs.big(), divisor(rhs)?.big()).map(Num::norm).ok_or(Error::DivisionByZero),
+		Ratio::div,
+		|lhs, rhs| lhs / rhs)
+}
+
+// the remainder of the truncated division
+fn rem(lhs: &Num, rhs: &Num) -> Result<Num, Error> {
+	arith(lhs, rhs,
+		|lhs, rhs| Ok(Num::Int(binary(lhs, divisor(rhs)?, isize::checked_rem, |lhs, rhs| lhs.div_rem(rhs).unwrap_or_default().1))),
+		Ratio::rem,
+		|lhs, rhs| lhs % rhs)
+}
+
+// folds the items with `f` from the left, a single item is combined with
+// `unit` first so that `(subtract x)` is `-x`.
+fn fold<T: Clone>(items: Vec<T>, unit: T, f: impl Fn(&T, &T) -> Result<T, Error>) -> Result<T, Error> {
+	let (first, rest) = match &items[..] {
+		[] | [_] => (unit, &items[..]),
+		[first, rest @ ..] => (first.clone(), rest),
+	};
+	rest.iter().try_fold(first, |acc, n| f(&acc, n))
+}
+
+pub fn subr_add(args: Op, _env: Op) -> Result<Op, Error> {
+	fold(nums(args, 0)?, Num::long(0), add).map(Num::into_op)
+}
+
+pub fn subr_subtract(args: Op, _env: Op) -> Result<Op, Error> {
+	fold(nums(args, 1)?, Num::long(0), sub).map(Num::into_op)
+}
+
+pub fn subr_mul(args: Op, _env: Op) -> Result<Op, Error> {
+	fold(nums(args, 0)?, Num::long(1), mul).map(Num::into_op)
+}
+
+// exact on exact numbers, dividing a float by zero gives an infinity or NaN
+pub fn subr_div(args: Op, _env: Op) -> Result<Op, Error> {
+	fold(nums(args, 1)?, Num::long(1), div).map(Num::into_op)
+}
+
+// truncated integer division
+pub fn subr_quotient(args: Op, _env: Op) -> Result<Op, Error> {
+	let [lhs, rhs] = pair(ints(args, 2)?);
+	let quotient = binary(&lhs, divisor(&rhs)?, isize::checked_div, |lhs, rhs| lhs.div_rem(rhs).unwrap_or_default().0);
+	Ok(quotient.into_op())
+}
+
+// the result has the sign of the divisor
+pub fn subr_mod(args: Op, _env: Op) -> Result<Op, Error> {
+	let [lhs, rhs] = pair(nums(args, 2)?);
+	let rem = rem(&lhs, &rhs)?;
+	if !rem.is_zero() && rem.is_negative() != rhs.is_negative() {
+		return add(&rem, &rhs).map(Num::into_op)
+	}
+	Ok(rem.into_op())
+}
+
+// the result has the sign of the dividend
+pub fn subr_rem(args: Op, _env: Op) -> Result<Op, Error> {
+	let [lhs, rhs] = pair(nums(args, 2)?);
+	rem(&lhs, &rhs).map(Num::into_op)
+}
+
+pub fn subr_abs(args: Op, _env: Op) -> Result<Op, Error> {
+	let abs = match nums(args, 1)?.swap_remove(0) {
+		Num::Int(n) => Num::Int(n.abs()),
+		Num::Ratio(n) => Num::Ratio(n.abs()),
+		Num::Float(n) => Num::Float(n.abs()),
+	};
+	Ok(abs.into_op())
+}
+
+// NaN if any of the numbers is NaN, wherever it is
+pub fn subr_min(args: Op, _env: Op) -> Result<Op, Error> {
+	let nums = nums(args, 1)?;
+	Ok(nums.into_iter().reduce(|min, n| if n < min || n.is_nan() { n } else { min }).unwrap().into_op())
+}
+
+pub fn subr_max(args: Op, _env: Op) -> Result<Op, Error> {
+	let nums = nums(args, 1)?;
+	Ok(nums.into_iter().reduce(|max, n| if n > max || n.is_nan() { n } else { max }).unwrap().into_op())
+}
+
+// exact numbers to integer powers are exact, a negative exponent gives the
+// inverse
+pub fn subr_pow(args: Op, _env: Op) -> Result<Op, Error> {
+	let pow = match pair(nums(args, 2)?) {
+		[Num::Int(base), Num::Int(exp)] if !exp.is_negative() => Num::Int(pow_int(base, exp)?),
+		[base @ (Num::Int(_) | Num::Ratio(_)), Num::Int(exp)] => pow_ratio(base.ratio(), exp)?,
+		[base, exp] => Num::Float(base.float().powf(exp.float())),
+	};
+	Ok(pow.into_op())
+}
+
+// `exp` must not be negative
+fn pow_int(base: Int, exp: Int) -> Result<Int, Error> {
+	if exp.is_zero() {
+		return Ok(Int::Long(1))
+	}
+	let pow = match base {
+		Int::Long(0 | 1) => base,
+		Int::Long(-1) => Int::Long(if exp.is_odd() { -1 } else { 1 }),
+		_ => {
+			let exp = match exp {
+				Int::Long(exp) if (base.bits() - 1).saturating_mul(exp as usize) < MAX_BITS => exp as usize,
+				_ => return Err(Error::Overflow),
+			};
+			match base {
+				Int::Long(n) => pow(n, exp).map_or_else(|| Int::norm(BigInt::from(n).pow(exp)), Int::Long),
+				Int::Big(n) => Int::Big(n.pow(exp)),
+			}
+		}
+	};
+	Ok(pow)
+}
+
+fn pow_ratio(base: Ratio, exp: Int) -> Result<Num, Error> {
+	let base = match exp.is_negative() {
+		true => base.recip().ok_or(Error::DivisionByZero)?,
+		false => base,
+	};
+	if base.is_integer() {
+		return Ok(Num::Int(pow_int(Int::norm(base.numer().clone()), exp.abs())?))
+	}
+	let bits = base.numer().bits().max(base.denom().bits());
+	let exp = match exp {
+		Int::Long(exp) if (bits - 1).saturating_mul(exp.unsigned_abs()) < MAX_BITS => exp.unsigned_abs(),
+		_ => return Err(Error::Overflow),
+	};
+	Ok(Num::Ratio(base.pow(exp)))
+}
+
+// by squaring
+fn pow(mut base: isize, mut exp: usize) -> Option<isize> {
+	let mut acc: isize = 1;
+	while exp > 0 {
+		if exp & 1 == 1 {
+			acc = acc.checked_mul(base)?;
+		}
+		exp >>= 1;
+		if exp > 0 {
+			base = base.checked_mul(base)?;
+		}
+	}
+	Some(acc)
+}
+
+pub fn subr_bit_and(args: Op, _env: Op) -> Result<Op, Error> {
+	fold(ints(args, 0)?, Int::Long(-1), |lhs, rhs| Ok(binary(lhs, rhs, |lhs, rhs| Some(lhs & rhs), BigInt::and))).map(Int::into_op)
+}
+
+pub fn subr_bit_or(args: Op, _env: Op) -> Result<Op, Error> {
+	fold(ints(args, 0)?, Int::Long(0), |lhs, rhs| Ok(binary(lhs, rhs, |lhs, rhs| Some(lhs | rhs), BigInt::or))).map(Int::into_op)
+}
+
+pub fn subr_bit_xor(args: Op, _env: Op) -> Result<Op, Error> {
+	fold(ints(args, 0)?, Int::Long(0), |lhs, rhs| Ok(binary(lhs, rhs, |lhs, rhs| Some(lhs ^ rhs), BigInt::xor))).map(Int::into_op)
+}
+
+pub fn subr_bit_not(args: Op, _env: Op) -> Result<Op, Error> {
+	let not = match ints(args, 1)?.swap_remove(0) {
+		Int::Long(n) => Int::Long(!n),
+		Int::Big(n) => Int::norm(n.not()),
+	};
+	Ok(not.into_op())
+}
+
+pub fn subr_shift_left(args: Op, _env: Op) -> Result<Op, Error> {
+	let [n, by] = pair(ints(args, 2)?);
+	shift(n, by, false)
+}
+
+// arithmetic shift, the sign is kept
+pub fn subr_shift_right(args: Op, _env: Op) -> Result<Op, Error> {
+	let [n, by] = pair(ints(args, 2)?);
+	shift(n, by, true)
+}
+
+// shifts by a negative amount go the other way
+fn shift(n: Int, by: Int, right: bool) -> Result<Op, Error> {
+	let right = right != by.is_negative();
+	let by = match by {
+		Int::Long(by) => by.unsigned_abs(),
+		Int::Big(_) => usize::MAX,
+	};
+	let shifted = if right { shift_right(n, by) } else { shift_left(n, by)? };
+	Ok(shifted.into_op())
+}
+
+fn shift_left(n: Int, by: usize) -> Result<Int, Error> {
+	if n.is_zero() {
+		return Ok(n)
+	}
+	if by >= MAX_BITS {
+		return Err(Error::Overflow)
+	}
+	if let Int::Long(n) = n {
+		if by < isize::BITS as usize && (n << by) >> by == n {
+			return Ok(Int::Long(n << by))
+		}
+	}
+	Ok(Int::norm(n.big().shl(by)))
+}
+
+fn shift_right(n: Int, by: usize) -> Int {
+	match n {
+		Int::Long(n) => Int::Long(n >> by.min(isize::BITS as usize - 1)),
+		Int::Big(n) => Int::norm(n.shr(by)),
+	}
+}
+
+// every argument is compared with the next one
+macro_rules! compare {
+	($name:ident, $op:tt) => {
+		pub fn $name(args: Op, _env: Op) -> Result<Op, Error> {
+			let nums = nums(args, 2)?;
+			Ok(truth(nums.windows(2).all(|pair| pair[0] $op pair[1])))
+		}
+	};
+}
+
+compare!(subr_lt, <);
+compare!(subr_gt, >);
+compare!(subr_le, <=);
+compare!(subr_ge, >=);
+
+// `f` applied to a number as a float
+macro_rules! math {
+	($name:ident, $f:path) => {
+		pub fn $name(args: Op, _env: Op) -> Result<Op, Error> {
+			let n = nums(args, 1)?.swap_remove(0);
+			Ok(Op::float($f(n.float())))
+		}
+	};
+}
+
+math!(subr_sqrt, f64::sqrt);
+math!(subr_exp, f64::exp);
+math!(subr_sin, f64::sin);
+math!(subr_cos, f64::cos);
+math!(subr_tan, f64::tan);
+math!(subr_asin, f64::asin);
+math!(subr_acos, f64::acos);
+
+// `(atan y x)` is the angle of the point `(x, y)`
+pub fn subr_atan(args: Op, _env: Op) -> Result<Op, Error> {
+	let atan = match &nums(args, 1)?[..] {
+		[n] => n.float().atan(),
+		[y, x, ..] => y.float().atan2(x.float()),
+		[] => unreachable!(),
+	};
+	Ok(Op::float(atan))
+}
+
+// `(log x base)`, the natural logarithm without a base
+pub fn subr_log(args: Op, _env: Op) -> Result<Op, Error> {
+	let log = match &nums(args, 1)?[..] {
+		[n] => n.float().ln(),
+		[n, base, ..] => n.float().log(base.float()),
+		[] => unreachable!(),
+	};
+	Ok(Op::float(log))
+}
+
+pub fn subr_float(args: Op, _env: Op) -> Result<Op, Error> {
+	let n = nums(args, 1)?.swap_remove(0);
+	Ok(Op::float(n.float()))
+}
+
+// integers are kept, fractions are rounded by `ratio` and floats by `float`,
+// infinities and NaN overflow
+fn round(args: Op, ratio: fn(&Ratio) -> BigInt, float: fn(f64) -> f64) -> Result<Op, Error> {
+	match nums(args, 1)?.swap_remove(0) {
+		Num::Int(n) => Ok(n.into_op()),
+		Num::Ratio(n) => Ok(Op::big(ratio(&n))),
+		Num::Float(n) => BigInt::from_f64(float(n)).map(Op::big).ok_or(Error::Overflow),
+	}
+}
+
+pub fn subr_floor(args: Op, _env: Op) -> Result<Op, Error> {
+	round(args, Ratio::floor, f64::floor)
+}
+
+pub fn subr_ceil(args: Op, _env: Op) -> Result<Op, Error> {
+	round(args, Ratio::ceil, f64::ceil)
+}
+
+// halfway cases go to the even neighbour
+pub fn subr_round(args: Op, _env: Op) -> Result<Op, Error> {
+	round(args, Ratio::round, f64::round_ties_even)
+}
+
+pub fn subr_truncate(args: Op, _env: Op) -> Result<Op, Error> {
+	round(args, Ratio::trunc, f64::trunc)
+}
+
+// the denominator of an integer is 1
+fn exact(args: Op) -> Result<Ratio, Error> {
+	let n = args.head().ok_or(Error::TooFewArgs)?;
+	match Num::from_op(n) {
+		Some(n @ (Num::Int(_) | Num::Ratio(_))) => Ok(n.ratio()),
+		_ => Err(Error::RequireExact(n)),
+	}
+}
+
+pub fn subr_numerator(args: Op, _env: Op) -> Result<Op, Error> {
+	Ok(Op::big(exact(args)?.numer().clone()))
+}
+
+pub fn subr_denominator(args: Op, _env: Op) -> Result<Op, Error> {
+	Ok(Op::big(exact(args)?.denom().clone()))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::eval::tests::run;
+
+	#[test]
+	fn min_and_max_propagate_nan() {
+		let nan = "(define nan (div 0.0 0.0))";
+		for src in ["(min nan 1)", "(min 1 nan)", "(max nan 1)", "(max 1 nan)", "(min 1 nan 0)", "(max 2 3 nan)"] {
+			assert_eq!(run(&format!("{nan} {src}")), "NaN", "{src}");
+		}
+		assert_eq!(run("(min 3 1/2 2.5)"), "1/2");
+		assert_eq!(run("(max 3 1/2 2.5)"), "3");
+	}
+}
@@ -0,0 +1,248 @@
+use super::Error;
+use crate::{Object, Op, nil, cons, intern};
+use std::collections::HashMap;
+
+// `...` can not be read, `etc` is the default ellipsis
+const ELLIPSIS: &str = "etc";
+
+#[derive(Clone)]
+enum Binding {
+	One(Op),
+	Many(Vec<Binding>),
+}
+
+type Bindings = HashMap<Op, Binding>;
+
+// parsed form of `[ellipsis] (literal ...) (pattern template) ...`
+struct Rules {
+	ellipsis: Op,
+	literals: Op,
+	rules: Op,
+	env: Op,
+}
+
+impl Rules {
+	fn parse(rules: Op, env: Op) -> Result<Self, Error> {
+		let (ellipsis, rules) = match rules.head() {
+			Some(ellipsis) if ellipsis.is_symbol() => (ellipsis, rules.get_tail_unchecked()),
+			_ => (intern(ELLIPSIS.into()), rules),
+		};
+		let Some(literals) = rules.head() else {
+			return Err(Error::TooFewArgs)
+		};
+		Ok(Self { ellipsis, literals, rules: rules.get_tail_unchecked(), env })
+	}
+
+	fn is_literal(&self, symbol: Op) -> bool {
+		self.literals.iter().any(|literal| literal == symbol)
+	}
+
+	fn matches(&self, pattern: Op, form: Op, binds: &mut Bindings) -> bool {
+		if pattern.is_symbol() {
+			if self.is_literal(pattern) {
+				return base(form) == pattern
+			}
+			if *pattern.get_symbol_unchecked() != *"_" {
+				binds.insert(pattern, Binding::One(form));
+			}
+			return true
+		}
+		if pattern.is_pair() {
+			return self.matches_list(pattern, form, binds)
+		}
+		super::num::eqv(pattern, form)
+	}
+
+	fn matches_list(&self, pattern: Op, form: Op, binds: &mut Bindings) -> bool {
+		let patterns: Vec<Op> = pattern.iter().collect();
+		let mut list = form.iter();
+		let forms: Vec<Op> = list.by_ref().collect();
+		if !list.rest().is_null() || !(form.is_pair() || form.is_null()) {
+			return false
+		}
+		let Some(at) = patterns.iter().position(|&p| p == self.ellipsis) else {
+			return patterns.len() == forms.len()
+				&& patterns.iter().zip(&forms).all(|(&p, &f)| self.matches(p, f, binds))
+		};
+		if at == 0 {
+			return false
+		}
+		// `before` patterns, the repeated one, its ellipsis and `after` patterns
+		let (before, after) = (at - 1, patterns.len() - at - 1);
+		if forms.len() < before + after {
+			return false
+		}
+		let repeated = forms.len() - before - after;
+		let prefix = patterns[..before].iter().zip(&forms[..before]);
+		let suffix = patterns[at + 1..].iter().zip(&forms[before + repeated..]);
+		if !prefix.chain(suffix).all(|(&p, &f)| self.matches(p, f, binds)) {
+			return false
+		}
+		let mut vars = Vec::new();
+		self.variables(patterns[at - 1], &mut vars);
+		let mut many: Vec<Vec<Binding>> = vec![Vec::new(); vars.len()];
+		for &form in &forms[before..before + repeated] {
+			let mut inner = Bindings::new();
+			if !self.matches(patterns[at - 1], form, &mut inner) {
+				return false
+			}
+			for (var, many) in vars.iter().zip(&mut many) {
+				many.push(inner.remove(var).unwrap_or(Binding::One(nil())));
+			}
+		}
+		binds.extend(vars.into_iter().zip(many.into_iter().map(Binding::Many)));
+		true
+	}
+
+	fn variables(&self, pattern: Op, vars: &mut Vec<Op>) {
+		if pattern.is_symbol() {
+			if !self.is_literal(pattern) && pattern != self.ellipsis && *pattern.get_symbol_unchecked() != *"_" {
+				vars.push(pattern);
+			}
+		} else if pattern.is_pair() {
+			pattern.iter().for_each(|pattern| self.variables(pattern, vars));
+		}
+	}
+
+	fn expand(&self, template: Op, binds: &Bindings, renames: &mut Bindings) -> Result<Op, Error> {
+		if template.is_symbol() {
+			return match binds.get(&template) {
+				Some(Binding::One(op)) => Ok(*op),
+				Some(Binding::Many(_)) => Err(Error::BadSyntax(template)),
+				None => Ok(self.rename(template, renames)),
+			}
+		}
+		if !template.is_pair() {
+			return Ok(template)
+		}
+		// `(etc etc)` stands for a literal ellipsis
+		if template.get_head_unchecked() == self.ellipsis {
+			return Ok(self.ellipsis)
+		}
+		let mut items = Vec::new();
+		let mut list = template;
+		while list.is_pair() {
+			let item = list.get_head_unchecked();
+			list = list.get_tail_unchecked();
+			if list.head() != Some(self.ellipsis) {
+				items.push(self.expand(item, binds, renames)?);
+				continue
+			}
+			list = list.get_tail_unchecked();
+			let mut vars = Vec::new();
+			self.variables(item, &mut vars);
+			let mut count = None;
+			for var in &vars {
+				if let Some(Binding::Many(many)) = binds.get(var) {
+					if count.is_some_and(|count| count != many.len()) {
+						return Err(Error::BadSyntax(template))
+					}
+					count = Some(many.len());
+				}
+			}
+			let Some(count) = count else {
+				return Err(Error::BadSyntax(template))
+			};
+			for index in 0..count {
+				let mut inner = binds.clone();
+				for var in &vars {
+					if let Some(Binding::Many(many)) = binds.get(var) {
+						inner.insert(*var, many[index].clone());
+					}
+				}
+				items.push(self.expand(item, &inner, renames)?);
+			}
+		}
+		let tail = self.expand(list, binds, renames)?;
+		Ok(items.into_iter().rev().fold(tail, |tail, head| cons(head, tail)))
+	}
+
+	// symbols introduced by the template get the same fresh alias within one
+	// expansion.
+	fn rename(&self, symbol: Op, renames: &mut Bindings) -> Op {
+		if let Some(Binding::One(alias)) = renames.get(&symbol) {
+			return *alias
+		}
+		let s = symbol.get_symbol_unchecked();
+		let alias = Op::new(Object::Symbol { s, alias: Some((symbol, self.env)) });
+		renames.insert(symbol, Binding::One(alias));
+		alias
+	}
+}
+
+/// Expands `form` with the first rule whose pattern matches it.
+pub fn expand(rules: Op, env: Op, form: Op) -> Result<Op, Error> {
+	let rules = Rules::parse(rules, env)?;
+	for rule in rules.rules.iter() {
+		let pattern = rule.head().ok_or(Error::RequirePair(rule))?;
+		let template = rule.get_tail_unchecked().head().ok_or(Error::TooFewArgs)?;
+		let mut binds = Bindings::new();
+		// the keyword position of the pattern is ignored
+		let (pattern, form) = (pattern.tail().unwrap_or(nil()), form.tail().unwrap_or(nil()));
+		if rules.matches(pattern, form, &mut binds) {
+			return rules.expand(template, &binds, &mut Bindings::new())
+		}
+	}
+	Err(Error::BadSyntax(form))
+}
+
+/// The symbol an alias stands for.
+pub fn base(mut op: Op) -> Op {
+	while let Some(Object::Symbol { alias: Some((symbol, _)), .. }) = op.get() {
+		op = symbol;
+	}
+	op
+}
+
+/// Replaces the aliases in `datum` by their base symbol, e.g. for `quote`.
+pub fn strip(datum: Op) -> Op {
+	if datum.is_symbol() {
+		return base(datum)
+	}
+	if !datum.is_pair() {
+		return datum
+	}
+	let mut list = datum.iter();
+	let items: Vec<(Op, Op)> = list.by_ref().map(|item| (item, strip(item))).collect();
+	let tail = strip(list.rest());
+	if tail == list.rest() && items.iter().all(|(item, stripped)| item == stripped) {
+		return datum
+	}
+	items.into_iter().rev().fold(tail, |tail, (_, head)| cons(head, tail))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::eval::tests::run;
+
+	const SWAP: &str = "(define swap (syntax_rules () ((_ a b) (let ((tmp a)) (set a b) (set b tmp)))))";
+	const MY_OR: &str = "(define my_or (syntax_rules ()
+		((_) false)
+		((_ e) e)
+		((_ e r etc) (let ((t e)) (if t t (my_or r etc))))))";
+
+	#[test]
+	fn introduced_bindings_do_not_capture() {
+		assert_eq!(run(&format!("{SWAP} (define tmp 1) (define y 2) (swap tmp y) `(~tmp ~y)")), "(2 1)");
+		assert_eq!(run(&format!("{SWAP} (let ((tmp 3) (x 4)) (swap x tmp) `(~x ~tmp))")), "(3 4)");
+		assert_eq!(run(&format!("{MY_OR} (let ((t 5)) (my_or false t))")), "5");
+	}
+
+	#[test]
+	fn free_symbols_refer_to_the_definition() {
+		assert_eq!(run(&format!("{MY_OR} (let ((if (lambda (a b c) 0))) (my_or false 7))")), "7");
+		assert_eq!(run(&format!("{MY_OR} (define f (lambda (if) (my_or false if))) (f 8)")), "8");
+	}
+
+	#[test]
+	fn ellipsis_matches_any_number_of_forms() {
+		let sum = "(define sum (syntax_rules () ((_ x etc) (add 0 x etc))))";
+		assert_eq!(run(&format!("{sum} (sum)")), "0");
+		assert_eq!(run(&format!("{sum} (sum 1)")), "1");
+		assert_eq!(run(&format!("{sum} (sum 1 2 3 4)")), "10");
+		let my_let = "(define my_let (syntax_rules () ((_ ((n v) etc) body) ((lambda (n etc) body) v etc))))";
+		assert_eq!(run(&format!("{my_let} (my_let ((a 1) (b 2) (c 3)) (add a b c))")), "6");
+		assert_eq!(run(&format!("{MY_OR} (my_or)")), "nil");
+		assert_eq!(run(&format!("{MY_OR} (my_or false false false 9 false)")), "9");
+	}
+}
@@ -0,0 +1,254 @@
+use crate::{Object, Op, NIL, context};
+use core::mem;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+// ids of the heaps, 0 is left for nil
+static HEAPS: AtomicU32 = AtomicU32::new(1);
+
+// least number of allocations between two automatic collections
+const THRESHOLD: usize = 4096;
+
+struct Slot {
+	gen: u32,
+	marked: bool,
+	obj: Object,
+}
+
+// slots are only ever appended, an object keeps its index until it is freed
+// and the slot's generation is bumped.
+pub struct Heap {
+	// the handles of a heap carry its id, so that a handle used with the
+	// heap of another interpreter is detected
+	id: u32,
+	slots: Vec<Slot>,
+	free: Vec<u32>,
+	allocated: usize,
+	// allocations before the next automatic collection, grows with the live
+	// objects so that collecting stays proportional to allocating
+	limit: usize,
+}
+
+impl Default for Heap {
+	fn default() -> Self {
+		Self { id: HEAPS.fetch_add(1, Ordering::Relaxed), slots: Vec::new(), free: Vec::new(), allocated: 0, limit: THRESHOLD }
+	}
+}
+
+impl Heap {
+	pub fn alloc(&mut self, obj: Object) -> Op {
+		self.allocated += 1;
+		if let Some(index) = self.free.pop() {
+			let slot = &mut self.slots[index as usize];
+			slot.obj = obj;
+			return Op { index, gen: slot.gen, heap: self.id }
+		}
+		let index = u32::try_from(self.slots.len()).ok()
+			.filter(|&index| index != NIL)
+			.expect("heap exhausted");
+		self.slots.push(Slot { gen: 0, marked: false, obj });
+		Op { index, gen: 0, heap: self.id }
+	}
+
+	pub fn id(&self) -> u32 {
+		self.id
+	}
+
+	fn slot(&self, op: Op) -> &Slot {
+		assert!(op.heap == self.id, "object used with another interpreter");
+		let slot = &self.slots[op.index as usize];
+		assert!(slot.gen == op.gen, "object used after being collected");
+		slot
+	}
+
+	pub fn get(&self, op: Op) -> &Object {
+		&self.slot(op).obj
+	}
+
+	pub fn get_mut(&mut self, op: Op) -> &mut Object {
+		self.slot(op);
+		&mut self.slots[op.index as usize].obj
+	}
+
+	fn mark(&mut self, roots: Vec<Op>) {
+		let mut pending = roots;
+		while let Some(op) = pending.pop() {
+			if op.is_null() {
+				continue
+			}
+			let slot = &mut self.slots[op.index as usize];
+			if mem::replace(&mut slot.marked, true) {
+				continue
+			}
+			match &slot.obj {
+				&Object::Pair { head, tail } => {
+					pending.push(head);
+					pending.push(tail);
+				}
+				&Object::Expr { def, env } => {
+					pending.push(def);
+					pending.push(env);
+				}
+				&Object::Macro { expander } => {
+					pending.push(expander);
+				}
+				&Object::Syntax { rules, env } => {
+					pending.push(rules);
+					pending.push(env);
+				}
+				&Object::Symbol { alias: Some((symbol, env)), .. } => {
+					pending.push(symbol);
+					pending.push(env);
+				}
+				Object::Cont { frames, .. } => {
+					frames.iter().for_each(|frame| frame.trace(&mut pending));
+				}
+				_ => {}
+			}
+		}
+	}
+
+	fn sweep(&mut self) -> usize {
+		let mut freed = 0;
+		for (index, slot) in self.slots.iter_mut().enumerate() {
+			if mem::take(&mut slot.marked) || matches!(slot.obj, Object::Free) {
+				continue
+			}
+			slot.obj = Object::Free;
+			slot.gen = slot.gen.wrapping_add(1);
+			self.free.push(index as u32);
+			freed += 1;
+		}
+		self.allocated = 0;
+		self.limit = THRESHOLD.max(2 * (self.slots.len() - self.free.len()));
+		freed
+	}
+}
+
+/// Keeps the pushed values alive across collections until it is dropped.
+pub struct Roots(usize);
+
+impl Roots {
+	pub fn open() -> Self {
+		Self(context(|ctx| ctx.roots.borrow().len()))
+	}
+
+	pub fn push(&self, op: Op) -> Op {
+		context(|ctx| ctx.roots.borrow_mut().push(op));
+		op
+	}
+
+	/// Releases the values pushed so far.
+	pub fn clear(&self) {
+		context(|ctx| ctx.roots.borrow_mut().truncate(self.0))
+	}
+}
+
+impl Drop for Roots {
+	fn drop(&mut self) {
+		context(|ctx| ctx.roots.borrow_mut().truncate(self.0))
+	}
+}
+
+/// Reclaims every object unreachable from the globals, the symbol table,
+/// the evaluator roots and stack and the pinned values, returns the number of
+/// freed objects.
+pub fn collect() -> usize {
+	context(|ctx| {
+		let mut roots = vec![ctx.globals.get()];
+		roots.extend(ctx.symbols.borrow().values());
+		roots.extend(ctx.roots.borrow().iter());
+		roots.extend(ctx.pinned.borrow().iter());
+		ctx.stack.borrow().iter().for_each(|frame| frame.trace(&mut roots));
+		let mut heap = ctx.heap.borrow_mut();
+		heap.mark(roots);
+		heap.sweep()
+	})
+}
+
+/// Collects when enough objects have been allocated since the last collection.
+pub fn safepoint() {
+	let due = context(|ctx| {
+		let heap = ctx.heap.borrow();
+		heap.allocated >= heap.limit
+	});
+	if due {
+		collect();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{collect, Roots};
+	use crate::{Interpreter, BaseReader, Reader as _, Op, context, nil, pin, unpin};
+
+	// the value of the last expression of `src`
+	fn eval(src: &str) -> Op {
+		let mut reader = BaseReader::new(src);
+		let mut value = nil();
+		while let Ok(op) = reader.read() {
+			value = crate::eval(op).unwrap();
+		}
+		value
+	}
+
+	fn is_live(op: Op) -> bool {
+		context(|ctx| ctx.heap.borrow().slots[op.index as usize].gen == op.gen)
+	}
+
+	#[test]
+	fn rooted_values_survive_collections() {
+		Interpreter::new().enter(|| {
+			let pinned = eval("'(1 2 3)");
+			pin(pinned);
+			let roots = Roots::open();
+			let rooted = roots.push(eval("'(4 5)"));
+			eval("(gc)");
+			assert_eq!(format!("{pinned:?} {rooted:?}"), "(1 2 3) (4 5)");
+			drop(roots);
+			collect();
+			assert!(is_live(pinned));
+			assert!(!is_live(rooted));
+		})
+	}
+
+	#[test]
+	#[should_panic(expected = "object used after being collected")]
+	fn collected_values_cannot_be_used() {
+		Interpreter::new().enter(|| {
+			let list = eval("'(1 2 3)");
+			pin(list);
+			unpin(list);
+			collect();
+			list.is_pair();
+		})
+	}
+
+	#[test]
+	fn reused_slots_change_generation() {
+		Interpreter::new().enter(|| {
+			// the last allocated object is the first slot reused
+			let old = Op::long(1);
+			collect();
+			let new = Op::long(2);
+			assert_eq!(old.index, new.index);
+			assert_ne!(old, new);
+			assert!(!is_live(old));
+			assert_eq!(format!("{new:?}"), "2");
+		})
+	}
+
+	#[test]
+	fn cycles_are_reclaimed() {
+		Interpreter::new().enter(|| {
+			// the closure is bound in the scope it closes over
+			let f = eval("(define f (let ((g 0)) (set g (lambda (x) g)) g)) f");
+			let scope = f.get_env_unchecked();
+			assert_eq!(format!("{:?}", eval("(eq (f 0) f)")), "true");
+			collect();
+			assert!(is_live(f) && is_live(scope));
+			eval("(set f 0)");
+			collect();
+			assert!(!is_live(f) && !is_live(scope));
+		})
+	}
+}
@@ -1,179 +1,284 @@
 mod read;
 mod eval;
+mod gc;
+mod value;
+mod convert;
+mod bigint;
+mod ratio;
 
 pub use eval::{Error as EvalError};
 pub use read::{Reader, Error as ParseError, BaseReader, SugarReader};
-use core::cell::RefCell;
+pub use value::{Value, List};
+pub use convert::{FromValue, IntoValue, HostFn};
+pub use bigint::BigInt;
+pub use ratio::Ratio;
+use core::cell::{Cell, RefCell};
 use core::fmt::{self, Debug};
 use core::ptr;
+use std::collections::HashMap;
+use std::rc::Rc;
 
-#[derive(Debug)]
+#[derive(Clone)]
 enum Object {
 	Long(isize),
+	// only for integers that do not fit a long
+	Big(Rc<BigInt>),
+	// only for fractions that are not integers
+	Ratio(Rc<Ratio>),
+	Float(f64),
 	Symbol {
-		s: String,
+		s: Rc<str>,
+		// a symbol renamed by a pattern macro refers to the original symbol
+		// in the macro's environment unless it gets bound by the expansion
+		alias: Option<(Op, Op)>,
 	},
 	Pair {
-		head: *const Object,
-		tail: *const Object,
+		head: Op,
+		tail: Op,
 	},
 	Expr {
-		def: *const Object,
-		env: *const Object,
+		def: Op,
+		env: Op,
 	},
 	Subr {
-		imp: PrimFun,
-		name: String,
+		imp: Imp,
+		name: Rc<str>,
 		is_fixed: bool,
 	},
+	// an `Expr` applied to the unevaluated arguments, the result is evaluated
+	Macro {
+		expander: Op,
+	},
+	// a pattern macro, `rules` are the arguments given to `syntax_rules`
+	Syntax {
+		rules: Op,
+		env: Op,
+	},
+	// the frames of the machine run `run` when the continuation was captured
+	Cont {
+		frames: Rc<[eval::Frame]>,
+		run: usize,
+	},
+	Free,
+}
+
+type PrimFun = Rc<dyn Fn(Op, Op) -> Result<Op, EvalError>>;
+type Builtin = fn(Op, Op) -> Result<Op, EvalError>;
+type FormFun = fn(Op, Op) -> Result<eval::Tail, EvalError>;
+
+// special forms may leave an expression in tail position to the evaluator
+#[derive(Clone)]
+enum Imp {
+	Prim(PrimFun),
+	Form(FormFun),
 }
 
-type PrimFun = fn(Op, Op) -> Result<Op, EvalError>;
+/// Handle to an object in the heap.
+///
+/// Objects never move, a handle stays valid for as long as the object is
+/// reachable, the generation detects handles outliving their object and the
+/// heap id handles used with another interpreter.
+/// Outside of its interpreter a handle prints as `#<op index:gen>`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Op {
+	index: u32,
+	gen: u32,
+	heap: u32,
+}
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub struct Op(*const Object);
+const NIL: u32 = u32::MAX;
 
 #[allow(unused)]
 impl Op {
 	fn new(obj: Object) -> Self {
-		OBJECTS.with(|cell| {
-			let mut vec = cell.borrow_mut();
-			vec.push(obj);
-			Self(vec.last().unwrap() as _)
-		})
+		context(|ctx| ctx.heap.borrow_mut().alloc(obj))
 	}
 
-	fn long(n: isize) -> Self {
+	pub fn long(n: isize) -> Self {
 		Self::new(Object::Long(n))
 	}
 
-	fn symbol(s: String) -> Self {
-		Self::new(Object::Symbol { s })
+	/// An integer, a long if `n` fits one.
+	pub fn big(n: BigInt) -> Self {
+		match n.to_isize() {
+			Some(n) => Self::long(n),
+			None => Self::new(Object::Big(Rc::new(n))),
+		}
 	}
 
-	fn pair(head: Self, tail: Self) -> Self {
-		Self::new(Object::Pair { head: head.0, tail: tail.0 })
+	/// An exact fraction, an integer if `n` is one.
+	pub fn ratio(n: Ratio) -> Self {
+		if n.is_integer() {
+			return Self::big(n.numer().clone())
+		}
+		Self::new(Object::Ratio(Rc::new(n)))
+	}
+
+	pub fn float(n: f64) -> Self {
+		Self::new(Object::Float(n))
+	}
+
+	pub fn pair(head: Self, tail: Self) -> Self {
+		Self::new(Object::Pair { head, tail })
 	}
 
 	fn expr(def: Self, env: Self) -> Self {
-		Self::new(Object::Expr { def: def.0, env: env.0 })
+		Self::new(Object::Expr { def, env })
 	}
 
-	fn subr(imp: PrimFun, name: String, is_fixed: bool) -> Self {
-		Self::new(Object::Subr { imp, name, is_fixed })
+	fn subr(imp: Imp, name: String, is_fixed: bool) -> Self {
+		Self::new(Object::Subr { imp, name: name.into(), is_fixed })
+	}
+
+	fn mac(expander: Self) -> Self {
+		Self::new(Object::Macro { expander })
+	}
+
+	fn syntax(rules: Self, env: Self) -> Self {
+		Self::new(Object::Syntax { rules, env })
+	}
+
+	fn cont(frames: Rc<[eval::Frame]>, run: usize) -> Self {
+		Self::new(Object::Cont { frames, run })
 	}
 
 	#[inline]
-	fn is_null(&self) -> bool {
-		self.0.is_null()
+	pub fn is_null(&self) -> bool {
+		self.index == NIL
+	}
+
+	// whether the object can be read, values are printed as their handle
+	// outside of their interpreter
+	fn is_entered(&self) -> bool {
+		let ctx = CURRENT.with(Cell::get);
+		// the context is kept alive by the `Interpreter::enter` borrow
+		!ctx.is_null() && unsafe { &*ctx }.heap.borrow().id() == self.heap
+	}
+
+	// the object must not be kept nor the heap modified inside `f`
+	fn with<R>(&self, f: impl FnOnce(Option<&Object>) -> R) -> R {
+		if self.is_null() {
+			return f(None)
+		}
+		context(|ctx| f(Some(ctx.heap.borrow().get(*self))))
+	}
+
+	fn get(&self) -> Option<Object> {
+		self.with(|obj| obj.cloned())
 	}
 
-	fn as_ref<'a>(&self) -> Option<&'a Object> {
-		if self.is_null() { None } else { unsafe { Some(&*self.0) } }
+	fn get_unchecked(&self) -> Object {
+		self.with(|obj| obj.expect("nil dereferenced").clone())
 	}
 
-	fn as_ref_unchecked<'a>(&self) -> &'a Object {
-		unsafe { &*self.0 }
+	fn update_unchecked(&self, f: impl FnOnce(&mut Object)) {
+		context(|ctx| f(ctx.heap.borrow_mut().get_mut(*self)))
 	}
 
-	fn as_mut<'a>(&self) -> Option<&'a mut Object> {
-		if self.is_null() { None } else { unsafe { Some(&mut *(self.0 as *mut _)) } }
+	pub fn is_long(&self) -> bool {
+		self.with(|obj| matches!(obj, Some(Object::Long(..))))
 	}
 
-	fn as_mut_unchecked<'a>(&self) -> &'a mut Object {
-		unsafe { &mut *(self.0 as *mut _) }
+	pub fn is_symbol(&self) -> bool {
+		self.with(|obj| matches!(obj, Some(Object::Symbol { .. })))
 	}
 
-	fn is_long(&self) -> bool {
-		matches!( self.as_ref(), Some(Object::Long(..)) )
+	pub fn is_pair(&self) -> bool {
+		self.with(|obj| matches!(obj, Some(Object::Pair { .. })))
 	}
 
-	fn is_symbol(&self) -> bool {
-		matches!( self.as_ref(), Some(Object::Symbol { .. }) )
+	pub fn is_expr(&self) -> bool {
+		self.with(|obj| matches!(obj, Some(Object::Expr { .. })))
 	}
 
-	fn is_pair(&self) -> bool {
-		matches!( self.as_ref(), Some(Object::Pair { .. }) )
+	pub fn is_subr(&self) -> bool {
+		self.with(|obj| matches!(obj, Some(Object::Subr { .. })))
 	}
 
-	fn is_expr(&self) -> bool {
-		matches!( self.as_ref(), Some(Object::Expr { .. }) )
+	pub fn is_macro(&self) -> bool {
+		self.with(|obj| matches!(obj, Some(Object::Macro { .. } | Object::Syntax { .. })))
 	}
 
-	fn is_subr(&self) -> bool {
-		matches!( self.as_ref(), Some(Object::Subr { .. }) )
+	pub fn is_cont(&self) -> bool {
+		self.with(|obj| matches!(obj, Some(Object::Cont { .. })))
 	}
 
 	fn get_long_unchecked(&self) -> isize {
-		match self.as_ref_unchecked() {
-			Object::Long(n) => *n,
-			_ => unsafe { core::hint::unreachable_unchecked() }
-		}
+		self.with(|obj| match obj {
+			Some(Object::Long(n)) => *n,
+			_ => unreachable!()
+		})
 	}
 
-	fn get_symbol_unchecked<'a>(&self) -> &'a String {
-		match self.as_ref_unchecked() {
-			Object::Symbol { s } => s,
-			_ => unsafe { core::hint::unreachable_unchecked() }
-		}
+	fn get_symbol_unchecked(&self) -> Rc<str> {
+		self.with(|obj| match obj {
+			Some(Object::Symbol { s, .. }) => s.clone(),
+			_ => unreachable!()
+		})
 	}
 
 	fn get_head_unchecked(&self) -> Self {
-		match self.as_ref_unchecked() {
-			Object::Pair { head, .. } => { Self(*head) }
-			_ => unsafe { core::hint::unreachable_unchecked() }
-		}
+		self.with(|obj| match obj {
+			Some(Object::Pair { head, .. }) => *head,
+			_ => unreachable!()
+		})
 	}
 
 	fn set_head_unchecked(&self, op: Op) {
-		match self.as_mut_unchecked() {
-			Object::Pair { head, .. } => { *head = op.0 }
-			_ => unsafe { core::hint::unreachable_unchecked() }
-		}
+		self.update_unchecked(|obj| match obj {
+			Object::Pair { head, .. } => { *head = op }
+			_ => unreachable!()
+		})
 	}
 
 	fn get_tail_unchecked(&self) -> Self {
-		match self.as_ref_unchecked() {
-			Object::Pair { tail, .. } => { Self(*tail) }
-			_ => unsafe { core::hint::unreachable_unchecked() }
-		}
+		self.with(|obj| match obj {
+			Some(Object::Pair { tail, .. }) => *tail,
+			_ => unreachable!()
+		})
 	}
 
 	fn set_tail_unchecked(&self, op: Op) {
-		match self.as_mut_unchecked() {
-			Object::Pair { tail, .. } => { *tail = op.0 }
-			_ => unsafe { core::hint::unreachable_unchecked() }
-		}
+		self.update_unchecked(|obj| match obj {
+			Object::Pair { tail, .. } => { *tail = op }
+			_ => unreachable!()
+		})
 	}
 
 	fn get_env_unchecked(&self) -> Self {
-		match self.as_ref_unchecked() {
-			Object::Expr { env, .. } => { Self(*env) }
-			_ => unsafe { core::hint::unreachable_unchecked() }
-		}
+		self.with(|obj| match obj {
+			Some(Object::Expr { env, .. }) => *env,
+			_ => unreachable!()
+		})
 	}
 
 	fn set_env_unchecked(&self, op: Op) {
-		match self.as_mut_unchecked() {
-			Object::Expr { env, .. } => { *env = op.0 }
-			_ => unsafe { core::hint::unreachable_unchecked() }
-		}
+		self.update_unchecked(|obj| match obj {
+			Object::Expr { env, .. } => { *env = op }
+			_ => unreachable!()
+		})
 	}
 
 	fn get_is_fixed_unchecked(&self) -> bool {
-		match self.as_ref_unchecked() {
-			Object::Subr { is_fixed, .. } => *is_fixed,
-			_ => unsafe { core::hint::unreachable_unchecked() }
-		}
+		self.with(|obj| match obj {
+			Some(Object::Subr { is_fixed, .. }) => *is_fixed,
+			_ => unreachable!()
+		})
 	}
 }
 
 impl Debug for Op {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		if *self == unsafe { GLOBALS } {
+		if !self.is_null() && !self.is_entered() {
+			return write!(f, "#<op {}:{}>", self.index, self.gen)
+		}
+		let Some(_nesting) = Nesting::enter() else {
+			return write!(f, "...")
+		};
+		if *self == globals() {
 			return write!(f, "<globals>")
 		}
-		match self.as_ref() {
+		match self.get() {
 			None => {
 				write!(f, "nil")
 			}
@@ -185,29 +290,48 @@ impl Debug for Op {
 						write!(f, "{n}")
 					}
 				}
-				Object::Symbol { s } => {
+				Object::Big(n) => {
+					if f.alternate() {
+						write!(f, "Big {n}")
+					} else {
+						write!(f, "{n}")
+					}
+				}
+				Object::Ratio(n) => {
+					if f.alternate() {
+						write!(f, "Ratio {n}")
+					} else {
+						write!(f, "{n}")
+					}
+				}
+				Object::Float(n) => {
+					if f.alternate() {
+						write!(f, "Float {n:?}")
+					} else {
+						write!(f, "{n:?}")
+					}
+				}
+				Object::Symbol { s, .. } => {
 					if f.alternate() {
 						write!(f, "Symbol {:?}", s)
 					} else {
 						write!(f, "{s}")
 					}
 				}
-				Object::Pair { head, tail } => {
+				Object::Pair { mut head, mut tail } => {
 					if f.alternate() {
 						f.debug_struct("Pair")
-							.field("head", &Self(*head))
-							.field("tail", &Self(*tail))
+							.field("head", &head)
+							.field("tail", &tail)
 							.finish()
 					} else {
-						let mut head: Op = head.into();
-						let mut tail: Op = tail.into();
 						write!(f, "(")?;
 						loop {
 							write!(f, "{:?}", head)?;
 							if !tail.is_pair() {
 								break
 							}
-							if tail == unsafe { GLOBALS } {
+							if tail == globals() {
 								break
 							}
 							head = tail.get_head_unchecked();
@@ -223,16 +347,41 @@ impl Debug for Op {
 				Object::Expr { def, env } => {
 					if f.alternate() {
 						f.debug_struct("Expr")
-							.field("def", &Self(*def))
-							.field("env", &Self(*env))
+							.field("def", &def)
+							.field("env", &env)
 							.finish()
 					} else {
-						write!(f, "ƒ({})", &Self::from(def).get_head_unchecked())
+						write!(f, "ƒ({})", def.get_head_unchecked())
 					}
 				}
 				Object::Subr { name, .. } => {
 					write!(f, "Subr {:?}", name)
 				}
+				Object::Macro { expander } => {
+					if f.alternate() {
+						f.debug_struct("Macro")
+							.field("expander", &expander)
+							.finish()
+					} else {
+						write!(f, "macro {:?}", expander)
+					}
+				}
+				Object::Syntax { rules, env } => {
+					if f.alternate() {
+						f.debug_struct("Syntax")
+							.field("rules", &rules)
+							.field("env", &env)
+							.finish()
+					} else {
+						write!(f, "syntax {:?}", rules)
+					}
+				}
+				Object::Cont { frames, .. } => {
+					write!(f, "continuation/{}", frames.len())
+				}
+				Object::Free => {
+					write!(f, "<free>")
+				}
 			}
 		}
 	}
@@ -240,29 +389,43 @@ impl Debug for Op {
 
 impl fmt::Display for Op {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		if *self == unsafe { GLOBALS } {
-			return write!(f, "<globals>")
-		}
 		if self.is_null() {
 			return write!(f, "nil")
 		}
-		match self.as_ref_unchecked() {
+		if !self.is_entered() {
+			return write!(f, "#<op {}:{}>", self.index, self.gen)
+		}
+		let Some(_nesting) = Nesting::enter() else {
+			return write!(f, "...")
+		};
+		if *self == globals() {
+			return write!(f, "<globals>")
+		}
+		match self.get_unchecked() {
 			Object::Long(n) => {
 				write!(f, "{n}")
 			}
-			Object::Symbol { s } => {
+			Object::Big(n) => {
+				write!(f, "{n}")
+			}
+			Object::Ratio(n) => {
+				write!(f, "{n}")
+			}
+			// always with a fraction or an exponent, `1.0` rather than `1`
+			Object::Float(n) => {
+				write!(f, "{n:?}")
+			}
+			Object::Symbol { s, .. } => {
 				write!(f, "'{s}'")
 			}
-			Object::Pair { head, tail } => {
-				let mut head: Op = head.into();
-				let mut tail: Op = tail.into();
+			Object::Pair { mut head, mut tail } => {
 				write!(f, "[")?;
 				loop {
 					write!(f, "{}", head)?;
 					if !tail.is_pair() {
 						break
 					}
-					if tail == unsafe { GLOBALS } {
+					if tail == globals() {
 						break
 					}
 					head = tail.get_head_unchecked();
@@ -275,31 +438,58 @@ impl fmt::Display for Op {
 				write!(f, "]")
 			}
 			Object::Expr { def, .. } => {
-				let def: Op = def.into();
-				write!(f, "{}", def.get_tail_unchecked())
+				let body = def.get_tail_unchecked();
+				match body.tail() {
+					Some(rest) if rest.is_null() => write!(f, "{}", body.get_head_unchecked()),
+					_ => write!(f, "{}", body)
+				}
 			}
 			Object::Subr { name, .. } => {
 				write!(f, "<subr {name}>")
 			}
+			Object::Macro { expander } => {
+				write!(f, "<macro {expander}>")
+			}
+			Object::Syntax { rules, .. } => {
+				write!(f, "<syntax {rules}>")
+			}
+			Object::Cont { .. } => {
+				write!(f, "<continuation>")
+			}
+			Object::Free => {
+				write!(f, "<free>")
+			}
 		}
 	}
 }
 
-impl From<*const Object> for Op {
-	fn from(ptr: *const Object) -> Self {
-		Self(ptr)
+// printing recurses on the native stack for each value nested in the head of
+// a pair, deeper values are elided
+const MAX_PRINT_DEPTH: usize = 256;
+
+struct Nesting;
+
+impl Nesting {
+	fn enter() -> Option<Self> {
+		PRINT_DEPTH.with(|depth| {
+			if depth.get() >= MAX_PRINT_DEPTH {
+				return None
+			}
+			depth.set(depth.get() + 1);
+			Some(Self)
+		})
 	}
 }
 
-impl From<&*const Object> for Op {
-	fn from(ptr: &*const Object) -> Self {
-		Self(*ptr)
+impl Drop for Nesting {
+	fn drop(&mut self) {
+		PRINT_DEPTH.with(|depth| depth.set(depth.get() - 1))
 	}
 }
 
 #[inline]
 const fn nil() -> Op {
-	Op(ptr::null())
+	Op { index: NIL, gen: 0, heap: 0 }
 }
 
 #[inline]
@@ -307,60 +497,312 @@ fn cons(head: Op, tail: Op) -> Op {
 	Op::pair(head, tail)
 }
 
+// state of an interpreter instance
+struct Context {
+	heap: RefCell<gc::Heap>,
+	// symbol objects are shared, so that symbols can be compared by identity
+	symbols: RefCell<HashMap<Rc<str>, Op>>,
+	// values held by the evaluator on the native stack
+	roots: RefCell<Vec<Op>>,
+	// values held by the host
+	pinned: RefCell<Vec<Op>>,
+	// the control stack of the evaluator
+	stack: RefCell<Vec<eval::Frame>>,
+	// runs of the evaluator nested on the native stack, e.g. by primitives
+	// calling back
+	runs: RefCell<Vec<eval::Run>>,
+	serial: Cell<usize>,
+	globals: Cell<Op>,
+}
+
 thread_local! {
-	static OBJECTS: RefCell<Vec<Object>> = RefCell::new(Vec::with_capacity(10000));
+	static CURRENT: Cell<*const Context> = const { Cell::new(ptr::null()) };
+	static PRINT_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+fn context<R>(f: impl FnOnce(&Context) -> R) -> R {
+	let ctx = CURRENT.with(Cell::get);
+	assert!(!ctx.is_null(), "no interpreter entered");
+	// the context is kept alive by the `Interpreter::enter` borrow
+	f(unsafe { &*ctx })
 }
 
-static mut SYMBOLS: Op = nil();
-static mut GLOBALS: Op = nil();
+#[inline]
+fn globals() -> Op {
+	context(|ctx| ctx.globals.get())
+}
 
 fn intern(s: String) -> Op {
-	let mut list = unsafe { SYMBOLS };
-	while list.is_pair() {
-		let symbol = list.get_head_unchecked();
-		if symbol.get_symbol_unchecked() == &s {
-			return symbol
-		}
-		list = list.get_tail_unchecked();
+	if let Some(symbol) = lookup_symbol(&s) {
+		return symbol
 	}
-	let symbol = Op::symbol(s);
-	unsafe { SYMBOLS = cons(symbol, SYMBOLS) }
+	let s: Rc<str> = s.into();
+	let symbol = Op::new(Object::Symbol { s: s.clone(), alias: None });
+	context(|ctx| ctx.symbols.borrow_mut().insert(s, symbol));
 	symbol
 }
 
+/// Finds the symbol with the given name without interning it.
+pub fn lookup_symbol(s: &str) -> Option<Op> {
+	context(|ctx| ctx.symbols.borrow().get(s).copied())
+}
+
+/// Evaluates `op` in the global environment of the entered interpreter.
 pub fn eval(op: Op) -> Result<Op, EvalError> {
-	eval::eval(op, unsafe { GLOBALS })
+	eval::eval(op, globals())
 }
 
-pub fn init() {
-	let global_var = cons(intern("globals".into()), nil());
-	unsafe {
-		GLOBALS = cons(global_var, GLOBALS);
-		global_var.set_tail_unchecked(GLOBALS)
+/// Applies a closure or primitive to a list of already evaluated arguments.
+///
+/// Called from a primitive, a failure with [`EvalError::Resume`] is a
+/// continuation escaping through the primitive and must be returned as is.
+pub fn call(fun: Op, args: Op) -> Result<Op, EvalError> {
+	eval::call(fun, args)
+}
+
+/// Returns the value bound to `name` in the global environment.
+pub fn global(name: &str) -> Option<Op> {
+	let pair = eval::assoc(lookup_symbol(name)?, globals());
+	pair.tail()
+}
+
+/// Protects `op` from the garbage collector until a matching [`unpin`].
+pub fn pin(op: Op) {
+	context(|ctx| ctx.pinned.borrow_mut().push(op))
+}
+
+pub fn unpin(op: Op) {
+	context(|ctx| {
+		let mut pinned = ctx.pinned.borrow_mut();
+		if let Some(index) = pinned.iter().rposition(|&pinned| pinned == op) {
+			pinned.swap_remove(index);
+		}
+	})
+}
+
+/// An interpreter instance, owning its heap, symbol table and global environment.
+///
+/// Values, readers and `eval` work on the interpreter entered with
+/// [`Interpreter::enter`], using a value of one instance with another panics.
+pub struct Interpreter {
+	ctx: Box<Context>,
+}
+
+impl Interpreter {
+	pub fn new() -> Self {
+		let interp = Self {
+			ctx: Box::new(Context {
+				heap: RefCell::default(),
+				symbols: RefCell::default(),
+				roots: RefCell::default(),
+				pinned: RefCell::default(),
+				stack: RefCell::default(),
+				runs: RefCell::default(),
+				serial: Cell::new(0),
+				globals: Cell::new(nil()),
+			}),
+		};
+		interp.enter(init);
+		interp
+	}
+
+	/// Makes this interpreter the current one on this thread while `f` runs.
+	pub fn enter<R>(&self, f: impl FnOnce() -> R) -> R {
+		struct Restore(*const Context);
+
+		impl Drop for Restore {
+			fn drop(&mut self) {
+				CURRENT.with(|current| current.set(self.0))
+			}
+		}
+
+		let _restore = Restore(CURRENT.with(|current| current.replace(&*self.ctx)));
+		f()
+	}
+
+	pub fn eval(&self, op: Op) -> Result<Op, EvalError> {
+		self.enter(|| eval(op))
+	}
+
+	/// Calls `fun`, e.g. a closure obtained with [`Interpreter::global`],
+	/// with arguments converted from Rust values.
+	pub fn call(&self, fun: Op, args: impl IntoValue) -> Result<Op, EvalError> {
+		self.enter(|| call(fun, args.into_value()))
+	}
+
+	pub fn global(&self, name: &str) -> Option<Op> {
+		self.enter(|| global(name))
+	}
+
+	/// Keeps a value alive across evaluations, e.g. a callback stored by the host.
+	pub fn pin(&self, op: Op) {
+		self.enter(|| pin(op))
+	}
+
+	pub fn unpin(&self, op: Op) {
+		self.enter(|| unpin(op))
+	}
+
+	/// Defines a global primitive called with its argument list and the
+	/// calling environment, the arguments are left unevaluated if `is_fixed`.
+	///
+	/// Values captured by `imp` are not traced by the garbage collector.
+	pub fn register<F>(&self, name: &str, is_fixed: bool, imp: F)
+	where
+		F: Fn(Op, Op) -> Result<Op, EvalError> + 'static
+	{
+		self.enter(|| define_subr(name, Imp::Prim(Rc::new(imp)), is_fixed))
+	}
+}
+
+impl Default for Interpreter {
+	fn default() -> Self {
+		Self::new()
 	}
+}
 
-	let sub_routes: [(&str, PrimFun, bool); 17] = [
-		("define", eval::subr_define, true),
+fn init() {
+	let global_var = cons(intern("globals".into()), nil());
+	let env = cons(global_var, nil());
+	global_var.set_tail_unchecked(env);
+	context(|ctx| ctx.globals.set(env));
+
+	let forms: [(&str, FormFun, bool); 17] = [
+		("define", eval::form_define, true),
+		("set", eval::form_set, true),
+		("set_scope", eval::form_set_scope, true),
+		("apply", eval::form_apply, false),
+		("lambda_apply", eval::form_apply, false),
+		("let", eval::form_let, true),
+		("let_star", eval::form_let_star, true),
+		("letrec", eval::form_letrec, true),
+		("if", eval::form_if, true),
+		("cond", eval::form_cond, true),
+		("and", eval::form_and, true),
+		("or", eval::form_or, true),
+		("call_cc", eval::form_call_cc, false),
+		("call_with_current_continuation", eval::form_call_cc, false),
+		("try", eval::form_try, true),
+		("begin", eval::form_begin, true),
+		("do", eval::form_begin, true),
+	];
+
+	let sub_routes: [(&str, Builtin, bool); 56] = [
 		("lambda", eval::subr_lambda, true),
 		("lambda_lambda", eval::subr_lambda_lambda, true),
-		("set_scope", eval::subr_set_scope, true),
+		("macro", eval::subr_macro, true),
+		("defmacro", eval::subr_defmacro, true),
+		("syntax_rules", eval::subr_syntax_rules, true),
+		("macroexpand", eval::subr_macroexpand, false),
 		("get_scope", eval::subr_get_scope, false),
-		("apply", eval::subr_apply, false),
-		("lambda_apply", eval::subr_apply, false),
-		("add", eval::subr_add, false),
-		("subtract", eval::subr_subtract, false),
-		("mul", eval::subr_mul, false),
-		("div", eval::subr_div, false),
+		("quote", eval::subr_quote, true),
+		("quasiquote", eval::subr_quasiquote, true),
+		("not", eval::subr_not, false),
+		("eq", eval::subr_eq, false),
+		("lt", eval::num::subr_lt, false),
+		("gt", eval::num::subr_gt, false),
+		("le", eval::num::subr_le, false),
+		("ge", eval::num::subr_ge, false),
+		("add", eval::num::subr_add, false),
+		("subtract", eval::num::subr_subtract, false),
+		("mul", eval::num::subr_mul, false),
+		("div", eval::num::subr_div, false),
+		("quotient", eval::num::subr_quotient, false),
+		("mod", eval::num::subr_mod, false),
+		("rem", eval::num::subr_rem, false),
+		("abs", eval::num::subr_abs, false),
+		("min", eval::num::subr_min, false),
+		("max", eval::num::subr_max, false),
+		("pow", eval::num::subr_pow, false),
+		("bit_and", eval::num::subr_bit_and, false),
+		("bit_or", eval::num::subr_bit_or, false),
+		("bit_xor", eval::num::subr_bit_xor, false),
+		("bit_not", eval::num::subr_bit_not, false),
+		("shift_left", eval::num::subr_shift_left, false),
+		("shift_right", eval::num::subr_shift_right, false),
+		("numerator", eval::num::subr_numerator, false),
+		("denominator", eval::num::subr_denominator, false),
+		("float", eval::num::subr_float, false),
+		("floor", eval::num::subr_floor, false),
+		("ceil", eval::num::subr_ceil, false),
+		("round", eval::num::subr_round, false),
+		("truncate", eval::num::subr_truncate, false),
+		("sqrt", eval::num::subr_sqrt, false),
+		("exp", eval::num::subr_exp, false),
+		("log", eval::num::subr_log, false),
+		("sin", eval::num::subr_sin, false),
+		("cos", eval::num::subr_cos, false),
+		("tan", eval::num::subr_tan, false),
+		("asin", eval::num::subr_asin, false),
+		("acos", eval::num::subr_acos, false),
+		("atan", eval::num::subr_atan, false),
 		("new_list", eval::subr_new_list, false),
 		("list_append", eval::subr_list_append, false),
 		("list_prepend", eval::subr_list_prepend, false),
 		("list_count", eval::subr_list_count, false),
 		("list_index", eval::subr_list_index, false),
 		("list_map", eval::subr_list_map, false),
+		("raise", eval::subr_raise, false),
+		("gc", eval::subr_gc, false),
 	];
 
+	for (name, form, is_fixed) in forms {
+		define_subr(name, Imp::Form(form), is_fixed);
+	}
+
 	for (name, fun, is_fixed) in sub_routes {
-		let subr = Op::subr(fun, name.into(), is_fixed);
-		eval::define(intern(name.into()), subr, unsafe { GLOBALS });
+		define_subr(name, Imp::Prim(Rc::new(fun)), is_fixed);
+	}
+
+	let true_ = eval::truth(true);
+	eval::define(true_, true_, globals());
+	eval::define(intern("false".into()), eval::truth(false), globals());
+	eval::define(intern("nil".into()), nil(), globals());
+}
+
+fn define_subr(name: &str, imp: Imp, is_fixed: bool) {
+	let subr = Op::subr(imp, name.into(), is_fixed);
+	eval::define(intern(name.into()), subr, globals());
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{Interpreter, Op};
+
+	#[test]
+	fn values_print_outside_their_interpreter() {
+		let interp = Interpreter::new();
+		let add = interp.global("add").unwrap();
+		let error = interp.call(add, (1, "x")).unwrap_err();
+		assert!(format!("{error:?}").starts_with("RequireNumber(#<op "));
+		let undefined = interp.eval(interp.enter(|| Op::symbol("y"))).unwrap_err();
+		assert!(format!("{undefined:?}").starts_with("Undefined(#<op "));
+		let other = Interpreter::new();
+		other.enter(|| assert!(format!("{add} {add:?}").starts_with("#<op ")));
+		interp.enter(|| assert_eq!(format!("{error:?}"), "RequireNumber(x)"));
+	}
+
+	#[test]
+	fn deep_values_are_elided() {
+		Interpreter::new().enter(|| {
+			let mut nested = Op::long(0);
+			for _ in 0..200_000 {
+				nested = Op::list([nested]);
+			}
+			let expected = "(".repeat(256) + "..." + &")".repeat(256);
+			assert_eq!(format!("{nested:?}"), expected);
+			let expected = "[".repeat(256) + "..." + &"]".repeat(256);
+			assert_eq!(format!("{nested}"), expected);
+			assert!(format!("{nested:#?}").contains("..."));
+			assert_eq!(format!("{:?}", Op::list([nested, Op::long(1)])).len(), 2 * 256 + 3 + 2);
+		})
+	}
+
+	#[test]
+	#[should_panic(expected = "object used with another interpreter")]
+	fn values_are_bound_to_their_interpreter() {
+		let (first, second) = (Interpreter::new(), Interpreter::new());
+		let list = first.enter(|| Op::list([Op::long(1), Op::long(2)]));
+		second.enter(|| list.is_pair());
 	}
 }
@@ -0,0 +1,245 @@
+use crate::BigInt;
+use core::cmp::Ordering;
+use core::fmt;
+
+/// An exact fraction in lowest terms, the denominator is positive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ratio {
+	numer: BigInt,
+	denom: BigInt,
+}
+
+impl Ratio {
+	/// `numer / denom` in lowest terms, `None` if `denom` is zero.
+	pub fn new(numer: BigInt, denom: BigInt) -> Option<Self> {
+		if denom.is_zero() {
+			return None
+		}
+		let gcd = numer.gcd(&denom);
+		let (numer, denom) = (quotient(&numer, &gcd), quotient(&denom, &gcd));
+		if denom.is_negative() {
+			return Some(Self { numer: numer.neg(), denom: denom.neg() })
+		}
+		Some(Self { numer, denom })
+	}
+
+	pub fn numer(&self) -> &BigInt {
+		&self.numer
+	}
+
+	pub fn denom(&self) -> &BigInt {
+		&self.denom
+	}
+
+	pub fn is_integer(&self) -> bool {
+		self.denom == BigInt::from(1)
+	}
+
+	pub fn is_zero(&self) -> bool {
+		self.numer.is_zero()
+	}
+
+	pub fn is_negative(&self) -> bool {
+		self.numer.is_negative()
+	}
+
+	pub fn neg(&self) -> Self {
+		Self { numer: self.numer.neg(), denom: self.denom.clone() }
+	}
+
+	pub fn abs(&self) -> Self {
+		Self { numer: self.numer.abs(), denom: self.denom.clone() }
+	}
+
+	/// The inverse, `None` for zero.
+	pub fn recip(&self) -> Option<Self> {
+		if self.is_zero() {
+			return None
+		}
+		let (numer, denom) = (self.denom.clone(), self.numer.clone());
+		if denom.is_negative() {
+			return Some(Self { numer: numer.neg(), denom: denom.neg() })
+		}
+		Some(Self { numer, denom })
+	}
+
+	pub fn add(&self, other: &Self) -> Self {
+		let numer = self.numer.mul(&other.denom).add(&other.numer.mul(&self.denom));
+		Self::new(numer, self.denom.mul(&other.denom)).expect("nonzero denominator")
+	}
+
+	pub fn sub(&self, other: &Self) -> Self {
+		self.add(&other.neg())
+	}
+
+	pub fn mul(&self, other: &Self) -> Self {
+		Self::new(self.numer.mul(&other.numer), self.denom.mul(&other.denom)).expect("nonzero denominator")
+	}
+
+	/// `None` if `other` is zero.
+	pub fn div(&self, other: &Self) -> Option<Self> {
+		Self::new(self.numer.mul(&other.denom), self.denom.mul(&other.numer))
+	}
+
+	/// Remainder of the truncated division, `None` if `other` is zero.
+	pub fn rem(&self, other: &Self) -> Option<Self> {
+		let quotient = self.div(other)?.trunc();
+		Some(self.sub(&other.mul(&Self::from(quotient))))
+	}
+
+	pub fn pow(&self, exp: usize) -> Self {
+		// powers of coprime integers stay coprime
+		Self { numer: self.numer.pow(exp), denom: self.denom.pow(exp) }
+	}
+
+	pub fn trunc(&self) -> BigInt {
+		quotient(&self.numer, &self.denom)
+	}
+
+	pub fn floor(&self) -> BigInt {
+		let trunc = self.trunc();
+		if self.is_negative() && !self.is_integer() {
+			return trunc.sub(&BigInt::from(1))
+		}
+		trunc
+	}
+
+	pub fn ceil(&self) -> BigInt {
+		let trunc = self.trunc();
+		if !self.is_negative() && !self.is_integer() {
+			return trunc.add(&BigInt::from(1))
+		}
+		trunc
+	}
+
+	/// The nearest integer, halfway cases go to the even neighbour.
+	pub fn round(&self) -> BigInt {
+		let floor = self.floor();
+		let twice = self.sub(&Self::from(floor.clone())).numer.mul(&BigInt::from(2));
+		match twice.cmp(&self.denom) {
+			Ordering::Less => floor,
+			Ordering::Equal if !floor.is_odd() => floor,
+			_ => floor.add(&BigInt::from(1)),
+		}
+	}
+
+	/// The nearest float.
+	pub fn to_f64(&self) -> f64 {
+		// scaled so that the quotient has at least 64 bits, enough to round
+		let by = self.denom.bits() as isize - self.numer.bits() as isize + 64;
+		let (numer, denom) = match by {
+			0.. => (self.numer.shl(by as usize), self.denom.clone()),
+			_ => (self.numer.clone(), self.denom.shl(by.unsigned_abs())),
+		};
+		let (mut quotient, rem) = numer.div_rem(&denom).expect("nonzero denominator");
+		// the remainder only matters for rounding
+		if !rem.is_zero() && !quotient.is_odd() {
+			quotient = quotient.add(&BigInt::from(if self.is_negative() { -1 } else { 1 }));
+		}
+		// in two steps, 2^-by alone may not be a finite float
+		let half = (by / 2).clamp(i32::MIN as isize, i32::MAX as isize) as i32;
+		let rest = (by - half as isize).clamp(i32::MIN as isize, i32::MAX as isize) as i32;
+		quotient.to_f64() * 2f64.powi(-half) * 2f64.powi(-rest)
+	}
+}
+
+impl From<BigInt> for Ratio {
+	fn from(n: BigInt) -> Self {
+		Self { numer: n, denom: BigInt::from(1) }
+	}
+}
+
+impl Ord for Ratio {
+	fn cmp(&self, other: &Self) -> Ordering {
+		// the denominators are positive
+		self.numer.mul(&other.denom).cmp(&other.numer.mul(&self.denom))
+	}
+}
+
+impl PartialOrd for Ratio {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl fmt::Display for Ratio {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}/{}", self.numer, self.denom)
+	}
+}
+
+fn quotient(lhs: &BigInt, rhs: &BigInt) -> BigInt {
+	lhs.div_rem(rhs).expect("nonzero divisor").0
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Ratio;
+	use crate::BigInt;
+
+	fn ratio(numer: isize, denom: isize) -> Ratio {
+		Ratio::new(BigInt::from(numer), BigInt::from(denom)).unwrap()
+	}
+
+	fn int(n: isize) -> BigInt {
+		BigInt::from(n)
+	}
+
+	#[test]
+	fn lowest_terms_with_positive_denominator() {
+		assert_eq!(ratio(2, 4).to_string(), "1/2");
+		assert_eq!(ratio(3, -6).to_string(), "-1/2");
+		assert_eq!(ratio(-3, -6).to_string(), "1/2");
+		assert_eq!(ratio(0, -5).to_string(), "0/1");
+		assert!(ratio(-12, 4).is_integer());
+		assert_eq!(ratio(-12, 4).numer(), &int(-3));
+		assert_eq!(Ratio::new(int(1), int(0)), None);
+		assert_eq!(ratio(-2, 3).recip(), Some(ratio(-3, 2)));
+		assert_eq!(ratio(0, 3).recip(), None);
+	}
+
+	#[test]
+	fn arithmetic() {
+		assert_eq!(ratio(1, 6).add(&ratio(1, 3)), ratio(1, 2));
+		assert_eq!(ratio(1, 6).sub(&ratio(1, 2)), ratio(-1, 3));
+		assert_eq!(ratio(2, 3).mul(&ratio(3, 4)), ratio(1, 2));
+		assert_eq!(ratio(2, 3).div(&ratio(-4, 9)), Some(ratio(-3, 2)));
+		assert_eq!(ratio(2, 3).div(&ratio(0, 1)), None);
+		assert_eq!(ratio(-2, 3).pow(3), ratio(-8, 27));
+		// the sign of the dividend, like on integers
+		assert_eq!(ratio(7, 2).rem(&ratio(1, 1)), Some(ratio(1, 2)));
+		assert_eq!(ratio(-7, 2).rem(&ratio(4, 3)), Some(ratio(-5, 6)));
+		assert_eq!(ratio(1, 2).rem(&ratio(0, 1)), None);
+		assert!(ratio(-1, 2) < ratio(-1, 3) && ratio(1, 3) < ratio(1, 2));
+	}
+
+	#[test]
+	fn rounding() {
+		// the fraction, then its trunc, floor, ceil and round
+		for (numer, denom, rounded) in [
+			(7, 2, [3, 3, 4, 4]),
+			(-7, 2, [-3, -4, -3, -4]),
+			(5, 2, [2, 2, 3, 2]),
+			(-5, 2, [-2, -3, -2, -2]),
+			(7, 3, [2, 2, 3, 2]),
+			(-8, 3, [-2, -3, -2, -3]),
+			(1, 3, [0, 0, 1, 0]),
+			(-1, 3, [0, -1, 0, 0]),
+			(-4, 2, [-2, -2, -2, -2]),
+		] {
+			let n = ratio(numer, denom);
+			assert_eq!([n.trunc(), n.floor(), n.ceil(), n.round()], rounded.map(int), "{n}");
+		}
+	}
+
+	#[test]
+	fn to_f64() {
+		assert_eq!(ratio(1, 2).to_f64(), 0.5);
+		assert_eq!(ratio(-1, 3).to_f64(), -1.0 / 3.0);
+		assert_eq!(ratio(2, 3).to_f64(), 2.0 / 3.0);
+		let tiny = Ratio::new(BigInt::from(1), BigInt::from(1).shl(1074)).unwrap();
+		assert_eq!(tiny.to_f64(), f64::from_bits(1));
+		let huge = Ratio::new(BigInt::from(3).shl(1100), BigInt::from(2)).unwrap();
+		assert_eq!(huge.to_f64(), f64::INFINITY);
+	}
+}
@@ -1,7 +1,7 @@
 mod sugar;
 
 pub use sugar::SugarReader;
-use crate::{Op, nil, cons, intern};
+use crate::{Op, BigInt, Ratio, nil, cons, intern};
 use core::iter::Peekable;
 use core::str::{Chars, FromStr};
 
@@ -12,13 +12,34 @@ pub enum Error {
 	Unexpected(char),
 	UnexpectedEof,
 	UnsupportedChar(char),
+	// a malformed number literal, e.g. `0x1g` or `1/0`
+	InvalidNumber(String),
+	// lists or quotes nested deeper than `MAX_DEPTH`
+	TooDeep,
 }
 
+// reading recurses on the native stack for each nested item
+const MAX_DEPTH: usize = 512;
+
 pub trait Reader<'a> {
 	fn chars(&mut self) -> &mut Peekable<Chars<'a>>;
 
+	// nesting of the item being read
+	fn depth(&mut self) -> &mut usize;
+
 	fn read_item(&mut self) -> Result<Op, Error>;
 
+	// reads an item inside of the one being read
+	fn read_nested(&mut self) -> Result<Op, Error> {
+		if *self.depth() >= MAX_DEPTH {
+			return Err(Error::TooDeep)
+		}
+		*self.depth() += 1;
+		let item = self.read_item();
+		*self.depth() -= 1;
+		item
+	}
+
 	fn read(&mut self) -> Result<Op, Error> {
 		self.read_item()
 	}
@@ -27,7 +48,7 @@ pub trait Reader<'a> {
 		let mut head = nil();
 		let mut tail = head;
 		loop {
-			match self.read_item() {
+			match self.read_nested() {
 				Ok(op) => {
 					let op = cons(op, nil());
 					if head.is_null() {
@@ -45,7 +66,7 @@ pub trait Reader<'a> {
 					break
 				}
 				Err(Error::Eof) => return Err(Error::UnexpectedEof),
-				e @ _ => return e
+				e => return e
 			} 
 		}
 		if head.is_null() {
@@ -54,57 +75,66 @@ pub trait Reader<'a> {
 		Ok(head)
 	}
 
-	fn read_number(&mut self, lead: Option<char>) -> Result<Op, Error> {
+	// a number if one starts here, with an optional sign
+	fn read_number(&mut self) -> Result<Op, Error> {
 		let chars = self.chars();
-		let mut s = String::new();
-		if let Some(c) = lead {
-			s.push(c);
-		} else {
-			match *chars.peek().ok_or(Error::UnexpectedEof)? {
-				c @ '0'..='9' => {
-					chars.next();
-					s.push(c);
-				}
-				_ => return Err(Error::Continue)
-			}
+		let mut ahead = chars.clone();
+		let sign = ahead.next_if(|&c| c == '-' || c == '+');
+		if !ahead.peek().is_some_and(char::is_ascii_digit) {
+			return Err(sign.map_or(Error::Continue, Error::Unexpected))
 		}
-		while let Some(c) = chars.next_if(|&c| matches!(c, '0'..='9')) {
+		// the literal runs over the letters, digits and signs that follow, so
+		// that e.g. `12ab` or `12-3` is one malformed literal rather than two
+		let mut s = String::new();
+		while let Some(c) = chars.next_if(|&c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '/' | '-' | '+')) {
 			s.push(c);
 		}
-		Ok(Op::long(isize::from_str(&s[..]).unwrap()))
+		parse_number(&s).ok_or(Error::InvalidNumber(s))
 	}
 
-	fn read_symbol(&mut self, quoted: bool) -> Result<Op, Error> {
+	fn read_symbol(&mut self) -> Result<Op, Error> {
 		let chars = self.chars();
 		match *chars.peek().ok_or(Error::UnexpectedEof)? {
 			'0'..='9' | 'A'..='Z' | 'a'..='z' | '_' => {
 				let mut s = String::new();
-				s.push(unsafe { chars.next().unwrap_unchecked() });
-				while let Some(c) = chars.peek().cloned() {
-					match c {
-						'0'..='9' | 'A'..='Z' | 'a'..='z' | '_' => {
-							chars.next();
-							s.push(c);
-						}
-						'\'' => {
-							if quoted {
-								chars.next();
-							}
-							break
-						}
-						_ => {
-							if quoted {
-								chars.next();
-								return Err(Error::UnsupportedChar(c))
-							}
-							break
-						}
-					}
+				while let Some(c) = chars.next_if(|&c| matches!(c, '0'..='9' | 'A'..='Z' | 'a'..='z' | '_')) {
+					s.push(c);
 				}
 				Ok(intern(s))
 			}
-			c @ _ => Err(if quoted { Error::UnsupportedChar(c) } else { Error::Unexpected(c) })
+			c => Err(Error::Unexpected(c))
+		}
+	}
+
+	// `'name'` reads as the plain symbol `name` for compatibility, any other
+	// item preceded by `'` is quoted, e.g. `'name` or `'(1 2 3)`.
+	fn read_quote(&mut self) -> Result<Op, Error> {
+		let mut ahead = self.chars().clone();
+		let is_name = ahead.next_if(|&c| c.is_ascii_alphabetic() || c == '_').is_some();
+		while ahead.next_if(|&c| c.is_ascii_alphanumeric() || c == '_').is_some() {}
+		if is_name && ahead.next() == Some('\'') {
+			let symbol = self.read_symbol()?;
+			self.chars().next();
+			return Ok(symbol)
+		}
+		self.read_quoted("quote")
+	}
+
+	// `~item` and `~@item`, `,` is taken by the list separator
+	fn read_unquote(&mut self) -> Result<Op, Error> {
+		if self.chars().next_if_eq(&'@').is_some() {
+			return self.read_quoted("unquote_splicing")
 		}
+		self.read_quoted("unquote")
+	}
+
+	// reads the next item as `(name item)`
+	fn read_quoted(&mut self, name: &str) -> Result<Op, Error> {
+		let item = match self.read_nested() {
+			Err(Error::Eof) => return Err(Error::UnexpectedEof),
+			item => item?
+		};
+		Ok(cons(intern(name.into()), cons(item, nil())))
 	}
 
 	fn skip_spaces(&mut self) {
@@ -112,13 +142,78 @@ pub trait Reader<'a> {
 	}
 }
 
+// `None` if `literal` is malformed. Integers are decimal or prefixed by `0x`,
+// `0o` or `0b`, fractions are `n/d` and floats have a decimal point or an
+// exponent. Digits may be separated by single underscores, e.g. `1_000`.
+fn parse_number(literal: &str) -> Option<Op> {
+	let (negative, s) = match literal.strip_prefix('-') {
+		Some(s) => (true, s),
+		None => (false, literal.strip_prefix('+').unwrap_or(literal)),
+	};
+	let sign = |n: BigInt| if negative { n.neg() } else { n };
+	let prefixed = [("0x", 16), ("0o", 8), ("0b", 2)].into_iter()
+		.find_map(|(prefix, radix)| Some((s.strip_prefix(prefix)?, radix)));
+	if let Some((s, radix)) = prefixed {
+		return Some(Op::big(sign(integer(s, radix)?)))
+	}
+	if let Some((numer, denom)) = s.split_once('/') {
+		return Ratio::new(sign(integer(numer, 10)?), integer(denom, 10)?).map(Op::ratio)
+	}
+	if !s.contains(['.', 'e', 'E']) {
+		return Some(Op::big(sign(integer(s, 10)?)))
+	}
+	let (mantissa, exp) = match s.split_once(['e', 'E']) {
+		Some((mantissa, exp)) => (mantissa, Some(exp)),
+		None => (s, None),
+	};
+	let mut float = String::from(if negative { "-" } else { "" });
+	match mantissa.split_once('.') {
+		Some((int, fraction)) => {
+			float += &digits(int, 10)?;
+			float.push('.');
+			float += &digits(fraction, 10)?;
+		}
+		None => float += &digits(mantissa, 10)?,
+	}
+	if let Some(exp) = exp {
+		float.push('e');
+		let exp = match exp.strip_prefix('-') {
+			Some(exp) => {
+				float.push('-');
+				exp
+			}
+			None => exp.strip_prefix('+').unwrap_or(exp),
+		};
+		float += &digits(exp, 10)?;
+	}
+	f64::from_str(&float).ok().map(Op::float)
+}
+
+fn integer(s: &str, radix: u32) -> Option<BigInt> {
+	BigInt::from_digits(&digits(s, radix)?, radix)
+}
+
+// the digits of `s` without the separators, `None` unless `s` is digits in
+// `radix` with single underscores between them
+fn digits(s: &str, radix: u32) -> Option<String> {
+	let mut digits = String::new();
+	for part in s.split('_') {
+		if part.is_empty() || !part.chars().all(|c| c.is_digit(radix)) {
+			return None
+		}
+		digits += part;
+	}
+	Some(digits)
+}
+
 pub struct BaseReader<'a> {
-	chars: Peekable<Chars<'a>>
+	chars: Peekable<Chars<'a>>,
+	depth: usize,
 }
 
 impl<'a> BaseReader<'a> {
 	pub fn new(input: &'a str) -> Self {
-		Self { chars: input.chars().peekable() }
+		Self { chars: input.chars().peekable(), depth: 0 }
 	}
 }
 
@@ -128,30 +223,120 @@ impl<'a> Reader<'a> for BaseReader<'a> {
 		&mut self.chars
 	}
 
+	#[inline]
+	fn depth(&mut self) -> &mut usize {
+		&mut self.depth
+	}
+
 	fn read_item(&mut self) -> Result<Op, Error> {
 		self.skip_spaces();
+		match *self.chars.peek().ok_or(Error::Eof)? {
+			'(' => {
+				self.chars.next();
+				self.read_list(')')
+			}
+			'[' => {
+				self.chars.next();
+				self.read_list(']')
+			}
+			'0'..='9' | '-' | '+' => {
+				self.read_number()
+			}
+			'\'' => {
+				self.chars.next();
+				self.read_quote()
+			}
+			'`' => {
+				self.chars.next();
+				self.read_quoted("quasiquote")
+			}
+			'~' => {
+				self.chars.next();
+				self.read_unquote()
+			}
+			_ => {
+				self.read_symbol()
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Reader, BaseReader, Error};
+	use crate::Interpreter;
+
+	// the items of `src` as printed, or the first error
+	fn read_all<'a, R: Reader<'a>>(mut reader: R) -> Result<Vec<String>, Error> {
+		let mut items = Vec::new();
 		loop {
-			match *self.chars.peek().ok_or(Error::Eof)? {
-				'(' => {
-					self.chars.next();
-					return self.read_list(')')
-				}
-				'[' => {
-					self.chars.next();
-					return self.read_list(']')
-				}
-				c @ '0'..='9' => {
-					self.chars.next();
-					return self.read_number(Some(c));
-				}
-				'\'' => {
-					self.chars.next();
-					return self.read_symbol(true)
-				}
-				_ => {
-					return self.read_symbol(false)
-				}
+			match reader.read() {
+				Ok(op) => items.push(format!("{op:?}")),
+				Err(Error::Eof) => return Ok(items),
+				Err(error) => return Err(error),
 			}
 		}
 	}
+
+	fn read(src: &str) -> Result<Vec<String>, Error> {
+		Interpreter::new().enter(|| read_all(BaseReader::new(src)))
+	}
+
+	fn invalid(src: &str) -> String {
+		match read(src) {
+			Err(Error::InvalidNumber(s)) => s,
+			other => panic!("{src} read as {other:?}"),
+		}
+	}
+
+	#[test]
+	fn signs_inside_literals_are_malformed() {
+		assert_eq!(invalid("12-3"), "12-3");
+		assert_eq!(invalid("(1+2)"), "1+2");
+		assert_eq!(invalid("0x1e-5"), "0x1e-5");
+		assert_eq!(invalid("1e5-3"), "1e5-3");
+		assert_eq!(invalid("1e--5"), "1e--5");
+		assert_eq!(read("(12 -3 +4 1e-5 2E+3)").unwrap(), ["(12 -3 4 1e-5 2000.0)"]);
+	}
+
+	#[test]
+	fn deep_nesting_is_an_error() {
+		let deep = "(".repeat(200_000) + &")".repeat(200_000);
+		assert!(matches!(read(&deep), Err(Error::TooDeep)));
+		assert!(matches!(read(&"'".repeat(200_000)), Err(Error::TooDeep)));
+		let nested = "(".repeat(500) + &")".repeat(500);
+		assert!(read(&nested).is_ok());
+	}
+
+	#[test]
+	fn literals() {
+		for (src, printed) in [
+			("42", "42"),
+			("-42", "-42"),
+			("+42", "42"),
+			("1_000_000", "1000000"),
+			("0xdead_beef", "3735928559"),
+			("-0o17", "-15"),
+			("0b1010", "10"),
+			("123456789012345678901234567890", "123456789012345678901234567890"),
+			("-0x1_0000_0000_0000_0000", "-18446744073709551616"),
+			("6/4", "3/2"),
+			("-1/2", "-1/2"),
+			("4/2", "2"),
+			("1.5", "1.5"),
+			("-0.25", "-0.25"),
+			("1e3", "1000.0"),
+			("2E+2", "200.0"),
+			("1_0.5e-1", "1.05"),
+		] {
+			assert_eq!(read(src).unwrap(), [printed], "{src}");
+		}
+	}
+
+	#[test]
+	fn malformed_literals() {
+		for src in ["12ab", "1_", "1__0", "0x", "0x_ff", "0x1g", "0b102", "1/0", "1/", "1/2/3", "0x1/2", "1.", "1.2.3", "1e", "1e5.0", "1_e5"] {
+			assert_eq!(invalid(src), src);
+		}
+	}
 }
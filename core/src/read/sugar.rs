@@ -4,12 +4,13 @@ use core::iter::Peekable;
 use core::str::Chars;
 
 pub struct SugarReader<'a> {
-	chars: Peekable<Chars<'a>>
+	chars: Peekable<Chars<'a>>,
+	depth: usize,
 }
 
 impl<'a> SugarReader<'a> {
 	pub fn new(input: &'a str) -> Self {
-		Self { chars: input.chars().peekable() }
+		Self { chars: input.chars().peekable(), depth: 0 }
 	}
 
 	fn try_read_arg_list(&mut self) -> Result<Op, Error> {
@@ -22,18 +23,18 @@ impl<'a> SugarReader<'a> {
 	}
 
 	fn dollar_stmt(&mut self) -> Result<Op, Error> {
-		let name = match self.read_number(None) {
+		let name = match self.read_number() {
 			Ok(x) => x,
 			Err(Error::Continue) => {
-				self.read_symbol(false)?
+				self.read_symbol()?
 			},
-			e @ _ => return e
+			e => return e
 		};
 		self.skip_spaces();
 		match self.chars.peek() {
 			Some('=') => {
 				self.chars.next();
-				let value = self.read_item()?;
+				let value = self.read_nested()?;
 				Ok(cons(intern("set_scope".into()), cons(name, cons(value, nil()))))
 			}
 			_ => {
@@ -53,21 +54,17 @@ impl<'a> SugarReader<'a> {
 						self.skip_spaces();
 						match self.chars.next().ok_or(Error::UnexpectedEof)? {
 							'{' => {
-								let mut body = self.read_list('}')?;
-								// the `{}` is not an actual list when body contains only one item
-								// e.g. { (add 1 2) }
-								if body.get_tail_unchecked().is_null() {
-									body = body.get_head_unchecked();
-								}
-								Ok(cons(intern("lambda_lambda".into()), cons(body, nil())))
+								// the statements of the block are the body of the lambda
+								let body = self.read_list('}')?;
+								Ok(cons(intern("lambda_lambda".into()), body))
 							}
-							c @ _ => Err(Error::Unexpected(c))
+							c => Err(Error::Unexpected(c))
 						}
 					}
-					c @ _ => Err(Error::Unexpected(c))
+					c => Err(Error::Unexpected(c))
 				}
 			}
-			c @ _ => Err(Error::Unexpected(c))
+			c => Err(Error::Unexpected(c))
 		}
 	}
 }
@@ -78,43 +75,53 @@ impl<'a> Reader<'a> for SugarReader<'a> {
 		&mut self.chars
 	}
 
+	#[inline]
+	fn depth(&mut self) -> &mut usize {
+		&mut self.depth
+	}
+
 	fn read_item(&mut self) -> Result<Op, Error> {
 		self.skip_spaces();
-		loop {
-			match *self.chars.peek().ok_or(Error::Eof)? {
-				'(' => {
-					self.chars.next();
-					return self.read_list(')')
-				}
-				'[' => {
-					self.chars.next();
-					return self.read_list(']')
-				}
-				'$' => {
-					self.chars.next();
-					return self.dollar_stmt()
-				}
-				'@' => {
-					self.chars.next();
-					return self.at_stmt()
-				}
-				c @ '0'..='9' => {
-					self.chars.next();
-					return self.read_number(Some(c))
-				}
-				'\'' => {
-					self.chars.next();
-					return self.read_symbol(true)
-				}
-				_ => {
-					let symbol = self.read_symbol(false)?;
-					match self.try_read_arg_list() {
-						Ok(list) => {
-							return Ok(cons(symbol, list))
-						}
-						Err(Error::Continue) => return Ok(symbol),
-						e @ _ => return e
+		match *self.chars.peek().ok_or(Error::Eof)? {
+			'(' => {
+				self.chars.next();
+				self.read_list(')')
+			}
+			'[' => {
+				self.chars.next();
+				self.read_list(']')
+			}
+			'$' => {
+				self.chars.next();
+				self.dollar_stmt()
+			}
+			'@' => {
+				self.chars.next();
+				self.at_stmt()
+			}
+			'0'..='9' | '-' | '+' => {
+				self.read_number()
+			}
+			'\'' => {
+				self.chars.next();
+				self.read_quote()
+			}
+			'`' => {
+				self.chars.next();
+				self.read_quoted("quasiquote")
+			}
+			'~' => {
+				self.chars.next();
+				self.read_unquote()
+			}
+			_ => {
+				let symbol = self.read_symbol()?;
+				match self.try_read_arg_list() {
+					Ok(list) => {
+						Ok(cons(symbol, list))
 					}
+					Err(Error::Continue) => Ok(symbol),
+					e => e
 				}
 			}
 		}
@@ -0,0 +1,112 @@
+use crate::{Object, Op, BigInt, Ratio, nil, intern};
+use std::rc::Rc;
+
+/// Structural view of a value, as returned by [`Op::value`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Value {
+	Nil,
+	Long(isize),
+	/// An integer that does not fit a long.
+	Big(BigInt),
+	/// A fraction that is not an integer.
+	Ratio(Ratio),
+	Float(f64),
+	Symbol(Rc<str>),
+	Pair(Op, Op),
+	/// A closure created by `lambda`.
+	Expr,
+	/// A primitive, with its name.
+	Subr(Rc<str>),
+	/// A macro created by `macro`, `defmacro` or `syntax_rules`.
+	Macro,
+	/// A continuation captured by `call_cc`.
+	Cont,
+}
+
+/// Safe API for hosts building and inspecting values.
+///
+/// Like the readers, these work on the entered interpreter. Values that are
+/// not reachable from the global environment may be reclaimed by the next
+/// evaluation, accessing them afterwards panics.
+impl Op {
+	#[inline]
+	pub const fn nil() -> Self {
+		nil()
+	}
+
+	/// Returns the interned symbol named `name`.
+	pub fn symbol(name: &str) -> Self {
+		intern(name.into())
+	}
+
+	/// Builds a proper list out of `items`.
+	pub fn list(items: impl IntoIterator<Item = Op>) -> Self {
+		let items: Vec<Op> = items.into_iter().collect();
+		items.into_iter().rev().fold(nil(), |tail, head| Op::pair(head, tail))
+	}
+
+	pub fn value(&self) -> Value {
+		match self.get() {
+			None => Value::Nil,
+			Some(Object::Long(n)) => Value::Long(n),
+			Some(Object::Big(n)) => Value::Big((*n).clone()),
+			Some(Object::Ratio(n)) => Value::Ratio((*n).clone()),
+			Some(Object::Float(n)) => Value::Float(n),
+			Some(Object::Symbol { s, .. }) => Value::Symbol(s),
+			Some(Object::Pair { head, tail }) => Value::Pair(head, tail),
+			Some(Object::Expr { .. }) => Value::Expr,
+			Some(Object::Subr { name, .. }) => Value::Subr(name),
+			Some(Object::Macro { .. } | Object::Syntax { .. }) => Value::Macro,
+			Some(Object::Cont { .. }) => Value::Cont,
+			Some(Object::Free) => unreachable!(),
+		}
+	}
+
+	/// Truthiness used by conditionals, every value but `nil` is true.
+	pub fn is_true(&self) -> bool {
+		!self.is_null()
+	}
+
+	pub fn as_long(&self) -> Option<isize> {
+		self.is_long().then(|| self.get_long_unchecked())
+	}
+
+	pub fn as_symbol(&self) -> Option<Rc<str>> {
+		self.is_symbol().then(|| self.get_symbol_unchecked())
+	}
+
+	pub fn head(&self) -> Option<Op> {
+		self.is_pair().then(|| self.get_head_unchecked())
+	}
+
+	pub fn tail(&self) -> Option<Op> {
+		self.is_pair().then(|| self.get_tail_unchecked())
+	}
+
+	/// Iterates over the elements of a list, stopping at the first tail
+	/// that is not a pair.
+	pub fn iter(&self) -> List {
+		List(*self)
+	}
+}
+
+/// Iterator over the elements of a list, see [`Op::iter`].
+pub struct List(Op);
+
+impl List {
+	/// The part of the list not yet iterated, `nil` at the end of a proper list.
+	pub fn rest(&self) -> Op {
+		self.0
+	}
+}
+
+impl Iterator for List {
+	type Item = Op;
+
+	fn next(&mut self) -> Option<Op> {
+		let head = self.0.head()?;
+		self.0 = self.0.get_tail_unchecked();
+		Some(head)
+	}
+}
@@ -1,13 +1,9 @@
-use mlang::{Reader, ParseError};
+use mlang::{Reader, Interpreter, ParseError};
 use core::cell::RefCell;
 use wasm_bindgen::prelude::wasm_bindgen;
 
-#[wasm_bindgen(start)]
-pub fn main() {
-	mlang::init();
-}
-
 thread_local! {
+	static INTERP: Interpreter = Interpreter::new();
 	static READ: RefCell<String> = Default::default();
 	static EVAL: RefCell<String> = Default::default();
 	static PARSE_ERR: RefCell<String> = Default::default();
@@ -16,6 +12,10 @@ thread_local! {
 
 #[wasm_bindgen]
 pub fn read_and_eval(s: &str, sugar_syntax: bool) {
+	INTERP.with(|interp| interp.enter(|| rep(s, sugar_syntax)))
+}
+
+fn rep(s: &str, sugar_syntax: bool) {
 	let mut reader: Box<dyn Reader> = if sugar_syntax {
 		Box::new(mlang::SugarReader::new(s))
 	} else {